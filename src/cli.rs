@@ -1,6 +1,11 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use clap_complete::ArgValueCandidates;
+
+use crate::completions::{Shell, distro_candidates, group_candidates, package_candidates, target_candidates};
+
+use impaccable::distro::MergeStrategy;
 
 #[derive(Parser)]
 #[command(author, version, about="A declarative pacman wrapper", arg_required_else_help=true)]
@@ -11,8 +16,33 @@ pub struct Cli {
     #[arg(short, long, value_name="CONFIG_PATH")]
     pub config: Option<PathBuf>,
 
-    #[arg(short, long, value_name="TARGET_PATH")]
-    pub target: Option<PathBuf>
+    /// Path of the file storing the active target, can also be set with IMPACCABLE_TARGET
+    #[arg(long, value_name="TARGET_PATH")]
+    pub target_file: Option<PathBuf>,
+
+    /// Increase the log verbosity, can be repeated up to three times
+    #[arg(short, long, global=true, action=ArgAction::Count, conflicts_with="quiet")]
+    pub verbose: u8,
+
+    /// Decrease the log verbosity, can be repeated up to two times
+    #[arg(short, long, global=true, action=ArgAction::Count)]
+    pub quiet: u8,
+
+    /// Append debug logs to this file, can also be set with IMPACCABLE_LOG_FILE
+    #[arg(long, global=true, value_name="LOG_PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Operate on the system installed below this path instead of the running system, passed to pacman
+    #[arg(long, global=true, value_name="ROOT_PATH")]
+    pub root: Option<PathBuf>,
+
+    /// Use this pacman database instead of the one of the root, passed to pacman
+    #[arg(long, global=true, value_name="DB_PATH")]
+    pub dbpath: Option<PathBuf>,
+
+    /// Operate on the system installed below this path, including its pacman configuration, passed to pacman
+    #[arg(long, global=true, value_name="SYSROOT_PATH")]
+    pub sysroot: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -22,38 +52,133 @@ pub enum CliCommand {
         /// Remove packages not tracked by your configuration
         #[arg(long)]
         remove_untracked: bool,
+
+        /// Target to sync instead of the active target, e.g. to bootstrap another system with `--root`
+        #[arg(long, add=ArgValueCandidates::new(target_candidates))]
+        target: Option<String>,
+
+        /// Install the missing packages into a fresh system at `--root` with pacstrap
+        #[arg(long, requires="root", conflicts_with_all=["remove_untracked", "dbpath", "sysroot"])]
+        pacstrap: bool,
     },
 
     /// Determine what changes sync would apply
     Plan {
         /// Evaluate what changes sync with this flag would apply
         #[arg(long)]
-        remove_untracked: bool
+        remove_untracked: bool,
+
+        /// Evaluate against a snapshot taken with `impaccable snapshot` instead of the installed packages
+        #[arg(long, value_name="SNAPSHOT_PATH")]
+        installed_from: Option<PathBuf>,
+
+        /// Target to evaluate instead of the active target
+        #[arg(long, add=ArgValueCandidates::new(target_candidates))]
+        target: Option<String>,
+    },
+
+    /// Print a snapshot of the installed packages, e.g. `impaccable snapshot > host.toml`
+    Snapshot {
+        /// Target to evaluate the snapshot against in reports, defaults to the active target
+        #[arg(long, add=ArgValueCandidates::new(target_candidates))]
+        target: Option<String>,
+    },
+
+    /// Report the drift of machines from their targets, based on a directory of snapshots
+    Report {
+        #[arg(required=true, value_name="SNAPSHOT_DIR")]
+        snapshot_dir: PathBuf,
+
+        #[arg(short, long, value_enum, default_value_t=ReportFormat::Text)]
+        format: ReportFormat,
+
+        /// Minimum number of machines a package has to be untracked on to be suggested for a shared group
+        #[arg(long, default_value_t=3)]
+        min_machines: usize,
     },
+
+    /// Check whether the system is in sync with the active target.
+    /// Exits with 0 if in sync, 2 if drifted and 1 on error, without prompting for a missing config or active target
+    Status,
+
+    /// Check the configuration for errors like undefined variables or values making invalid package names. Exits with 1 if there are any
+    Check,
     
     /// Add packages to specified group
     Add {
         #[arg(required=true, num_args=1..)]
         packages: Vec<String>,
 
-        #[arg(short, long, required=true)]
+        #[arg(short, long, required=true, add=ArgValueCandidates::new(group_candidates))]
         group: String,
     },
 
     /// Remove a package from specified group
     Remove {
-        #[arg(required=true)]
+        #[arg(required=true, add=ArgValueCandidates::new(package_candidates))]
         package: String,
 
-        #[arg(short, long, required=true)]
+        #[arg(short, long, required=true, add=ArgValueCandidates::new(group_candidates))]
         group: String,
     },
 
-    /// Import packages from your system into your config (interactive)
-    Import,
+    /// Move packages from one group to another
+    Mv {
+        #[arg(required_unless_present="all", num_args=1.., add=ArgValueCandidates::new(package_candidates))]
+        packages: Vec<String>,
+
+        #[arg(long, required=true, add=ArgValueCandidates::new(group_candidates))]
+        from: String,
+
+        #[arg(long, required=true, add=ArgValueCandidates::new(group_candidates))]
+        to: String,
+
+        /// Move all packages of the group
+        #[arg(long, conflicts_with="packages")]
+        all: bool,
+    },
+
+    /// Explain where a package comes from: the groups declaring it or the configured packages requiring it
+    #[command(alias="which")]
+    Why {
+        #[arg(required=true, add=ArgValueCandidates::new(package_candidates))]
+        package: String,
+    },
+
+    /// Import packages from your system into your config.
+    /// Interactive, unless a group is passed
+    Import {
+        /// Group to import the packages into, selecting the packages with `--all` or `--match`
+        #[arg(short, long, add=ArgValueCandidates::new(group_candidates))]
+        group: Option<String>,
+
+        /// Package file to create the group in, relative to the package directory. Created if missing
+        #[arg(short, long, requires="create")]
+        file: Option<PathBuf>,
+
+        /// Create the group if it does not exist
+        #[arg(long, requires="group")]
+        create: bool,
+
+        /// Import all untracked packages matching the regex
+        #[arg(short='m', long="match", value_name="REGEX", requires="group")]
+        pattern: Option<String>,
+
+        /// Import all untracked packages
+        #[arg(long, requires="group", conflicts_with="pattern")]
+        all: bool,
+
+        /// Do not import packages matching the glob, may be passed multiple times
+        #[arg(long, value_name="GLOB", requires="group")]
+        exclude: Vec<String>,
+
+        /// Only show what would be imported
+        #[arg(long, requires="group")]
+        dry_run: bool,
+    },
 
     /// Get a package template for your Arch-based distro
-    Template,
+    Template(Template),
 
     /// Dump the configuration file
     Config,
@@ -62,7 +187,82 @@ pub enum CliCommand {
     Target(Target),
 
     #[command(subcommand)]
-    Groups(Groups)
+    Groups(Groups),
+
+    Export(Export),
+
+    /// Run git in the config directory, e.g. `impaccable git log`
+    Git {
+        #[arg(trailing_var_arg=true, allow_hyphen_values=true)]
+        args: Vec<String>,
+    },
+
+    /// Print the script registering shell completions, e.g. `source <(impaccable completions bash)`
+    Completions {
+        #[arg(required=true, value_enum)]
+        shell: Shell,
+    },
+}
+
+/// Get a package template for your Arch-based distro
+#[derive(Args)]
+#[command(args_conflicts_with_subcommands=true)]
+pub struct Template {
+    #[command(subcommand)]
+    pub command: Option<TemplateCommand>,
+
+    /// List the available distro providers
+    #[arg(long)]
+    pub list: bool,
+
+    #[command(flatten)]
+    pub source: TemplateSourceArgs,
+
+    /// How to merge changed groups into a previously templated file. Asks for every changed group if omitted
+    #[arg(long, value_enum)]
+    pub strategy: Option<MergeStrategy>,
+}
+
+#[derive(Subcommand)]
+pub enum TemplateCommand {
+    #[command(about="Compare the installed packages to the template of your distro")]
+    Diff {
+        #[command(flatten)]
+        source: TemplateSourceArgs,
+    },
+}
+
+/// Options selecting the distro provider and where it reads its package lists from
+#[derive(Args)]
+pub struct TemplateSourceArgs {
+    /// Distro provider to use instead of detecting it from /etc/os-release
+    #[arg(long, add=ArgValueCandidates::new(distro_candidates))]
+    pub distro: Option<String>,
+
+    /// Desktop to template instead of detecting it, e.g. `kde` or `gnome`
+    #[arg(long)]
+    pub desktop: Option<String>,
+
+    /// Read the package lists from a mirror URL, a `file://` URL or a local directory instead of upstream
+    #[arg(long, value_name="URL_OR_DIR")]
+    pub source: Option<String>,
+
+    /// Do not cache fetched package lists
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Timeout for fetching a package list in seconds
+    #[arg(long, value_name="SECONDS", default_value_t=30)]
+    pub timeout: u64,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    /// Human readable summary
+    Text,
+    Json,
+    /// Standalone HTML page
+    Html,
 }
 
 /// Interact with target configuration
@@ -74,16 +274,176 @@ pub enum Target {
     Get,
     #[command(about="Set the active target")]
     Set {
+        #[arg(required=true, add=ArgValueCandidates::new(target_candidates))]
+        target: String,
+
+        #[arg(long)]
+        force: bool,
+    },
+    #[command(about="Create a new target")]
+    Create {
         #[arg(required=true)]
         target: String,
 
+        /// Root groups of the new target
+        #[arg(short, long, num_args=1.., add=ArgValueCandidates::new(group_candidates))]
+        groups: Vec<String>,
+    },
+    #[command(about="Delete a target")]
+    Delete {
+        #[arg(required=true, add=ArgValueCandidates::new(target_candidates))]
+        target: String,
+
+        /// Also delete the target if it is the active target
         #[arg(long)]
         force: bool,
     },
+    #[command(about="Rename a target, updating the active target if required")]
+    Rename {
+        #[arg(required=true, add=ArgValueCandidates::new(target_candidates))]
+        target: String,
+
+        #[arg(required=true)]
+        new_name: String,
+    },
+    #[command(about="Copy a target to a new target with the same root groups")]
+    Copy {
+        #[arg(required=true, add=ArgValueCandidates::new(target_candidates))]
+        target: String,
+
+        #[arg(required=true)]
+        new_target: String,
+    },
+    #[command(about="Compare the packages of two targets or snapshots, showing the groups responsible for them")]
+    Diff {
+        /// Targets to compare, one for each side not replaced by a snapshot
+        #[arg(num_args=0..=2, add=ArgValueCandidates::new(target_candidates))]
+        targets: Vec<String>,
+
+        /// Snapshot taken with `impaccable snapshot` to compare as the first side instead of a target
+        #[arg(long, value_name="SNAPSHOT_PATH")]
+        snapshot_a: Option<PathBuf>,
+
+        /// Snapshot taken with `impaccable snapshot` to compare as the second side instead of a target
+        #[arg(long, value_name="SNAPSHOT_PATH")]
+        snapshot_b: Option<PathBuf>,
+    },
+    #[command(about="Add root groups to a target")]
+    AddGroup {
+        #[arg(required=true, add=ArgValueCandidates::new(target_candidates))]
+        target: String,
+
+        #[arg(required=true, num_args=1.., add=ArgValueCandidates::new(group_candidates))]
+        groups: Vec<String>,
+    },
+    #[command(about="Remove root groups from a target")]
+    RemoveGroup {
+        #[arg(required=true, add=ArgValueCandidates::new(target_candidates))]
+        target: String,
+
+        #[arg(required=true, num_args=1.., add=ArgValueCandidates::new(group_candidates))]
+        groups: Vec<String>,
+    },
 }
 
 /// Manage Groups
 #[derive(Subcommand)]
 pub enum Groups {
+    #[command(about="List all groups")]
     Ls,
-}
\ No newline at end of file
+    #[command(about="Show the file, members and targets of a group")]
+    Show {
+        #[arg(required=true, add=ArgValueCandidates::new(group_candidates))]
+        group: String,
+    },
+    #[command(about="Create a new, empty group")]
+    Create {
+        #[arg(required=true)]
+        group: String,
+
+        /// Package file to store the group in, relative to the package directory. Created if missing
+        #[arg(short, long, required=true)]
+        file: PathBuf,
+    },
+    #[command(about="Delete a group")]
+    Delete {
+        #[arg(required=true, add=ArgValueCandidates::new(group_candidates))]
+        group: String,
+
+        /// Also delete the group if it is a root group of a target, removing it from those targets
+        #[arg(long)]
+        force: bool,
+    },
+    #[command(about="Rename a group, updating all targets referencing it")]
+    Rename {
+        #[arg(required=true, add=ArgValueCandidates::new(group_candidates))]
+        group: String,
+
+        #[arg(required=true)]
+        new_name: String,
+    },
+    #[command(about="Move a group to another package file")]
+    Move {
+        #[arg(required=true, add=ArgValueCandidates::new(group_candidates))]
+        group: String,
+
+        /// Package file to move the group to, relative to the package directory. Created if missing
+        #[arg(long, required=true)]
+        to_file: PathBuf,
+    },
+}
+
+/// Export targets to other formats
+#[derive(Args)]
+#[command(args_conflicts_with_subcommands=true, subcommand_negates_reqs=true)]
+pub struct Export {
+    #[command(subcommand)]
+    pub command: Option<ExportCommand>,
+
+    /// Target to export the packages of
+    #[arg(required=true, add=ArgValueCandidates::new(target_candidates))]
+    pub target: Option<String>,
+
+    /// Format to export the packages of the target in
+    #[arg(short, long, value_enum, default_value_t=ExportFormat::Plain)]
+    pub format: ExportFormat,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    /// One package per line
+    Plain,
+    /// pacstrap command line installing into /mnt
+    Pacstrap,
+    /// archiso packages.x86_64 file
+    Archiso,
+    /// archinstall configuration file
+    ArchinstallJson,
+}
+
+#[derive(Subcommand)]
+pub enum ExportCommand {
+    #[command(about="Generate the PKGBUILD of a metapackage depending on the packages of a target")]
+    Pkgbuild {
+        #[arg(required=true, add=ArgValueCandidates::new(target_candidates))]
+        target: String,
+
+        /// Generate a metapackage per root group, and a metapackage for the target depending on them
+        #[arg(long, requires="output")]
+        per_group: bool,
+
+        /// Directory to write the PKGBUILDs to, in a subdirectory per package. Printed if omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Version of the metapackages, has to increase whenever the packages change.
+        /// Defaults to the number of commits changing the configuration if the config directory is a git repository
+        #[arg(long)]
+        pkgver: Option<String>,
+    },
+    #[command(about="Generate a Containerfile installing the packages of a target, skipping packages not in the official repositories")]
+    Containerfile {
+        #[arg(required=true, add=ArgValueCandidates::new(target_candidates))]
+        target: String,
+    },
+}
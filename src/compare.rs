@@ -0,0 +1,85 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{GroupId, PackageId, config::{PackageConfiguration, TargetConfig}, snapshot::Snapshot};
+
+/// Packages mapped to the groups responsible for them
+pub type PackageGroups = BTreeMap<PackageId, BTreeSet<GroupId>>;
+
+/// Resolves the packages of a target, mapped to the root groups declaring them.
+pub fn target_packages(package_config: &PackageConfiguration, target: &TargetConfig) -> PackageGroups {
+    let mut packages = PackageGroups::new();
+    for (group_id, group) in package_config.filter_groups(&target.root_groups) {
+        for package in &group.members {
+            packages.entry(package.clone()).or_default().insert(group_id.clone());
+        }
+    }
+    packages
+}
+
+/// Resolves the explicitly installed packages of a snapshot, mapped to the root groups of `target` declaring them.
+/// Packages not declared by any of the groups, or all packages if there is no target, map to no groups.
+pub fn snapshot_packages(snapshot: &Snapshot, package_config: &PackageConfiguration, target: Option<&TargetConfig>) -> PackageGroups {
+    let declared = target.map(|target| target_packages(package_config, target)).unwrap_or_default();
+    snapshot.explicit
+        .keys()
+        .map(|package| (package.clone(), declared.get(package).cloned().unwrap_or_default()))
+        .collect()
+}
+
+/// The differences between the packages of two targets or snapshots, named `a` and `b`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageDiff {
+    /// Packages of both sides, with the groups responsible on side `a` and side `b`
+    pub shared: BTreeMap<PackageId, (BTreeSet<GroupId>, BTreeSet<GroupId>)>,
+    pub only_a: PackageGroups,
+    pub only_b: PackageGroups,
+}
+
+impl PackageDiff {
+    pub fn new(mut a: PackageGroups, mut b: PackageGroups) -> Self {
+        let shared_packages : Vec<PackageId> = a.keys().filter(|package| b.contains_key(*package)).cloned().collect();
+        let shared = shared_packages
+            .into_iter()
+            .map(|package| {
+                let groups_a = a.remove(&package).unwrap_or_default();
+                let groups_b = b.remove(&package).unwrap_or_default();
+                (package, (groups_a, groups_b))
+            })
+            .collect();
+        Self { shared, only_a: a, only_b: b }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{package_config, set, snapshot};
+
+    #[test]
+    fn test_package_diff() {
+        let package_config = package_config(&[
+            ("base", &["bat", "helix"]),
+            ("desktop", &["firefox", "helix"]),
+            ("work", &["slack", "firefox"]),
+        ]);
+
+        let desktop = TargetConfig::from_root_groups(set(&["base", "desktop"]));
+        let laptop = TargetConfig::from_root_groups(set(&["base", "work"]));
+        let diff = PackageDiff::new(target_packages(&package_config, &desktop), target_packages(&package_config, &laptop));
+
+        let expected = PackageDiff {
+            shared: [
+                ("bat".to_owned(), (set(&["base"]), set(&["base"]))),
+                ("firefox".to_owned(), (set(&["desktop"]), set(&["work"]))),
+                ("helix".to_owned(), (set(&["base", "desktop"]), set(&["base"]))),
+            ].into(),
+            only_a: PackageGroups::new(),
+            only_b: [("slack".to_owned(), set(&["work"]))].into(),
+        };
+        assert_eq!(expected, diff);
+
+        let snapshot = snapshot(None, None, &["bat", "htop"]);
+        let expected_snapshot : PackageGroups = [("bat".to_owned(), set(&["base"])), ("htop".to_owned(), set(&[]))].into();
+        assert_eq!(expected_snapshot, snapshot_packages(&snapshot, &package_config, Some(&desktop)));
+    }
+}
@@ -0,0 +1,134 @@
+use std::{env, io::Write, path::PathBuf};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use clap_complete::{CompletionCandidate, env::{Bash, EnvCompleter, Fish, Zsh}};
+use impaccable::{config::ConfigManager, distro::DistroRegistry};
+
+/// Environment variable the registration scripts set to request completions
+pub const COMPLETE_VAR : &str = "COMPLETE";
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Writes the script registering the dynamic completions of impaccable with the shell.
+pub fn write_registration(shell: Shell, buf: &mut dyn Write) -> anyhow::Result<()> {
+    let completer : &dyn EnvCompleter = match shell {
+        Shell::Bash => &Bash,
+        Shell::Zsh => &Zsh,
+        Shell::Fish => &Fish,
+    };
+    // the shell calls back into this binary to complete, so it has to be found even if it is not in PATH
+    let completer_path = env::current_exe().context("Failed to get the path of the impaccable binary")?;
+    completer.write_registration(COMPLETE_VAR, "impaccable", "impaccable", &completer_path.to_string_lossy(), buf)
+        .context("Failed to write completion script")
+}
+
+/// Suggests the names of the configured targets.
+pub fn target_candidates() -> Vec<CompletionCandidate> {
+    let Some(config_manager) = completed_config() else {
+        return Vec::new();
+    };
+    config_manager.config().targets.keys().map(CompletionCandidate::new).collect()
+}
+
+/// Suggests the ids of all groups in the package configuration.
+pub fn group_candidates() -> Vec<CompletionCandidate> {
+    let Some(config_manager) = completed_config() else {
+        return Vec::new();
+    };
+    config_manager.package_config().iter_groups().map(|(group_id, _)| CompletionCandidate::new(group_id)).collect()
+}
+
+/// Suggests the members of the group selected with `--group` or `--from`, or all configured packages if no group is selected.
+pub fn package_candidates() -> Vec<CompletionCandidate> {
+    let Some(config_manager) = completed_config() else {
+        return Vec::new();
+    };
+    let package_config = config_manager.package_config();
+    let packages : Vec<&String> = match completed_arg_value(&["-g", "--group", "--from"]) {
+        Some(group_id) => package_config.find_group(&group_id)
+            .map(|(_, group)| group.members.iter().collect())
+            .unwrap_or_default(),
+        None => {
+            let mut packages : Vec<&String> = package_config.iter_groups().flat_map(|(_, group)| &group.members).collect();
+            packages.sort();
+            packages.dedup();
+            packages
+        },
+    };
+    packages.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Suggests the ids of the built-in distro providers.
+pub fn distro_candidates() -> Vec<CompletionCandidate> {
+    DistroRegistry::default()
+        .providers()
+        .map(|provider| CompletionCandidate::new(provider.id()).help(Some(provider.name().into())))
+        .collect()
+}
+
+/// Parses the configuration the command line being completed refers to.
+/// Completion must never fail loudly, so errors result in `None`.
+fn completed_config() -> Option<ConfigManager> {
+    let cli_override = completed_arg_value(&["-c", "--config"]).map(PathBuf::from);
+    let config_path = crate::config_path(cli_override, &crate::project_dirs().ok()?);
+    ConfigManager::parse(config_path).ok()
+}
+
+/// Returns the value of the first of the options `names` on the command line being completed.
+fn completed_arg_value(names: &[&str]) -> Option<String> {
+    // the shell passes the words of the command line being completed after `--`
+    let words : Vec<String> = env::args_os()
+        .skip_while(|arg| arg != "--")
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    arg_value(&words, names)
+}
+
+/// Returns the value of the first of the options `names` in `words`, passed as `--name value` or `--name=value`.
+fn arg_value(words: &[String], names: &[&str]) -> Option<String> {
+    words.iter().enumerate().find_map(|(i, word)| {
+        names.iter().find_map(|name| {
+            if word == name {
+                words.get(i + 1).filter(|value| !value.is_empty()).cloned()
+            } else {
+                word.strip_prefix(name).and_then(|value| value.strip_prefix('=')).map(|value| value.to_owned())
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arg_value() {
+        let words : Vec<String> = ["impaccable", "remove", "--group", "base", ""].map(String::from).to_vec();
+        assert_eq!(Some("base".to_owned()), arg_value(&words, &["-g", "--group"]));
+        assert_eq!(None, arg_value(&words, &["--from"]));
+
+        let words : Vec<String> = ["impaccable", "--config=/tmp/config.toml", "mv", "--from", ""].map(String::from).to_vec();
+        assert_eq!(Some("/tmp/config.toml".to_owned()), arg_value(&words, &["-c", "--config"]));
+        assert_eq!(None, arg_value(&words, &["--from"]));
+    }
+
+    #[test]
+    fn test_diff_snapshot_paths() {
+        use clap::CommandFactory;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("laptop.toml"), "").unwrap();
+
+        let args = ["impaccable", "target", "diff", "--snapshot-b", "lap"].map(std::ffi::OsString::from).to_vec();
+        let candidates = clap_complete::engine::complete(&mut crate::cli::Cli::command(), args, 4, Some(temp_dir.path())).unwrap();
+        let values : Vec<String> = candidates.iter().map(|candidate| candidate.get_value().to_string_lossy().into_owned()).collect();
+        assert_eq!(vec!["laptop.toml".to_owned()], values);
+    }
+}
@@ -0,0 +1,928 @@
+use std::{path::{PathBuf, Path}, collections::{HashMap, BTreeSet, BTreeMap, btree_map::Entry}, fs};
+
+use anyhow::{anyhow, bail};
+use serde::{Serialize, Deserialize};
+use walkdir::WalkDir;
+use std::io::Write;
+
+use super::{GroupId, Error, PackageId, PackageGroup, PackageGroupMap, variables::{self, InterpolationError, Variables}};
+
+use std::iter::Extend;
+
+/// Manages the configuration, persisting it to the config file when required.
+pub struct ConfigManager {
+    config_path: PathBuf,
+    config: Config,
+    package_config: PackageConfiguration
+}
+
+impl ConfigManager {
+
+    /// Tries to parse the configuration file at `config path` and the associated package directory
+    pub fn parse(config_path: PathBuf) -> crate::Result<Self> {
+        log::debug!("Parsing config file '{}'", config_path.to_string_lossy());
+        let config_string = fs::read_to_string(&config_path)
+            .map_err(|io_err| {
+                if io_err.kind() == std::io::ErrorKind::NotFound {
+                    Error::ConfigFileNotFound { path: config_path.clone(), source: io_err }
+                } else {
+                    io_err.into()
+                }
+            })?;
+
+        let config : Config = toml::from_str(&config_string)?;
+        let package_config_path = config_path.parent()
+            .ok_or(Error::ConfigFileHasNoParentDir{path: config_path.clone()})?
+            .join(&config.package_dir);
+        let package_config = PackageConfiguration::parse(&package_config_path)?;
+        log::debug!("Parsed {} targets and {} package files", config.targets.len(), package_config.files.len());
+
+        let package_group_names : BTreeSet<&GroupId> =
+            package_config.files
+                .values()
+                .flat_map(|contained_groups| contained_groups.groups.keys())
+                .collect();
+
+        // verify all groups configured in the config file actually exist in the package configuration
+        for target in config.targets.values() {
+            for configured_group in &target.root_groups {
+                if !package_group_names.contains(configured_group){
+                    return Err(Error::GroupNotFound { group: configured_group.to_owned() });
+                }
+            }
+        }
+
+        Ok(Self { config_path, config, package_config })
+    }
+
+    pub fn config(&self) -> &Config { &self.config }
+
+    /// Resolves the configuration of a target.
+    pub fn target(&self, target_id: &TargetId) -> crate::Result<&TargetConfig> {
+        self.config.targets.get(target_id).ok_or_else(|| Error::TargetNotFound(target_id.clone()))
+    }
+
+    pub fn package_config(&self) -> &PackageConfiguration { &self.package_config }
+
+    /// Resolves the package configuration of a target, with the variables of the target interpolated in its groups.
+    /// Groups of other targets are interpolated with the same variables where possible.
+    pub fn target_package_config(&self, target: &TargetConfig) -> crate::Result<PackageConfiguration> {
+        self.package_config.interpolated(&target.root_groups, &self.config.target_variables(target))
+    }
+    pub fn package_config_mut(&mut self) -> &mut PackageConfiguration { &mut self.package_config } 
+
+    /// Adds a new root group to the specified target configuration.
+    /// Returns `false` if no change was performed, i.e. the group was already present.
+    pub fn add_root_group(&mut self, target_id: &TargetId, group: GroupId) -> anyhow::Result<bool> {
+        if self.package_config.find_group(&group).is_none() {
+            bail!(Error::GroupNotFound { group })
+        }
+        if let Some(target_config) = self.config.targets.get_mut(target_id) {
+            let added = target_config.root_groups.insert(group);
+            if added {
+                self.write_config_to_disk()?;
+            }
+            Ok(added)
+        } else {
+            bail!(Error::TargetNotFound(target_id.clone()))
+        }
+    }
+
+    /// Removes a root group from the specified target configuration.
+    /// Returns `false` if no change was performed, i.e. the group was not a root group of the target.
+    pub fn remove_root_group(&mut self, target_id: &TargetId, group: &GroupId) -> anyhow::Result<bool> {
+        if let Some(target_config) = self.config.targets.get_mut(target_id) {
+            let removed = target_config.root_groups.remove(group);
+            if removed {
+                self.write_config_to_disk()?;
+            }
+            Ok(removed)
+        } else {
+            bail!(Error::TargetNotFound(target_id.clone()))
+        }
+    }
+
+    /// Creates a new target with the specified root groups, all of which have to exist.
+    pub fn create_target(&mut self, target_id: TargetId, target_config: TargetConfig) -> anyhow::Result<()> {
+        if let Some(group) = target_config.root_groups.iter().find(|group| self.package_config.find_group(group).is_none()) {
+            bail!(Error::GroupNotFound { group: group.clone() })
+        }
+        match self.config.targets.entry(target_id) {
+            Entry::Vacant(entry) => {
+                entry.insert(target_config);
+                self.write_config_to_disk()
+            },
+            Entry::Occupied(entry) => bail!(Error::TargetAlreadyExists(entry.key().clone())),
+        }
+    }
+
+    /// Deletes a target and returns its configuration.
+    pub fn delete_target(&mut self, target_id: &TargetId) -> anyhow::Result<TargetConfig> {
+        let Some(target_config) = self.config.targets.remove(target_id) else {
+            bail!(Error::TargetNotFound(target_id.clone()))
+        };
+        self.write_config_to_disk()?;
+        Ok(target_config)
+    }
+
+    /// Renames a target, keeping its configuration.
+    pub fn rename_target(&mut self, target_id: &TargetId, new_target_id: TargetId) -> anyhow::Result<()> {
+        if self.config.targets.contains_key(&new_target_id) {
+            bail!(Error::TargetAlreadyExists(new_target_id))
+        }
+        let Some(target_config) = self.config.targets.remove(target_id) else {
+            bail!(Error::TargetNotFound(target_id.clone()))
+        };
+        self.config.targets.insert(new_target_id, target_config);
+        self.write_config_to_disk()
+    }
+
+    /// Creates a new target with the same configuration as an existing one.
+    pub fn copy_target(&mut self, target_id: &TargetId, new_target_id: TargetId) -> anyhow::Result<()> {
+        let Some(target_config) = self.config.targets.get(target_id) else {
+            bail!(Error::TargetNotFound(target_id.clone()))
+        };
+        self.create_target(new_target_id, target_config.clone())
+    }
+
+    /// Renames a group in the package configuration and updates all targets referencing it.
+    /// If writing the config fails, the package files are rolled back.
+    pub fn rename_group(&mut self, group_id: &GroupId, new_group_id: GroupId) -> anyhow::Result<()> {
+        self.config_transaction(|package_config, config, config_path| {
+            package_config.rename_group(group_id, new_group_id.clone())?;
+
+            let mut config_changed = false;
+            for target_config in config.targets.values_mut() {
+                if target_config.root_groups.remove(group_id) {
+                    target_config.root_groups.insert(new_group_id.clone());
+                    config_changed = true;
+                }
+            }
+            if config_changed {
+                write_config(config_path, config)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Deletes a group from the package configuration and removes it from the root groups of all targets.
+    /// Returns the targets the group was removed from.
+    /// If writing the config fails, the package files are rolled back.
+    pub fn delete_group(&mut self, group_id: &GroupId) -> anyhow::Result<Vec<TargetId>> {
+        self.config_transaction(|package_config, config, config_path| {
+            package_config.delete_group(group_id)?;
+
+            let mut affected_targets = Vec::new();
+            for (target_id, target_config) in config.targets.iter_mut() {
+                if target_config.root_groups.remove(group_id) {
+                    affected_targets.push(target_id.clone());
+                }
+            }
+            if !affected_targets.is_empty() {
+                write_config(config_path, config)?;
+            }
+            Ok(affected_targets)
+        })
+    }
+
+    /// Changes the package configuration and the config together, with `f` writing the config itself.
+    /// If `f` fails, the package configuration is rolled back like by [`PackageConfiguration::transaction`] and the config restored in memory.
+    fn config_transaction<F, T>(&mut self, f: F) -> anyhow::Result<T>
+    where
+        F: FnOnce(&mut PackageConfiguration, &mut Config, &Path) -> crate::Result<T>
+    {
+        let previous_config = self.config.clone();
+        let (config, config_path) = (&mut self.config, &self.config_path);
+        let result = self.package_config.transaction(|package_config| f(package_config, config, config_path));
+        if result.is_err() {
+            self.config = previous_config;
+        }
+        Ok(result?)
+    }
+
+    /// Resolves the path of a package file relative to the package directory.
+    /// Appends the `toml` extension if the path has none. Absolute paths are kept as they are.
+    pub fn package_file_path(&self, file_path: &Path) -> anyhow::Result<PathBuf> {
+        let mut path = self.absolute_package_dir()?.join(file_path);
+        if path.extension().is_none() {
+            path.set_extension("toml");
+        }
+        Ok(path)
+    }
+
+    pub fn absolute_package_dir(&self) -> anyhow::Result<PathBuf> {
+        self.config_dir().map(|dir| dir.join(&self.config.package_dir))
+    }
+
+    pub fn config_path(&self) -> &Path { &self.config_path }
+
+    /// Returns the directory containing the config file.
+    pub fn config_dir(&self) -> anyhow::Result<&Path> {
+        config_dir(&self.config_path).ok_or(anyhow!("Failed to get directory containing config"))
+    }
+
+    /// Returns the paths to commit when the config directory is a git repository: the config file and the package directory.
+    /// The paths are relative to [`Self::config_dir`], as the config path may be relative to the working directory.
+    pub fn tracked_paths(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let config_file = self.config_path
+            .file_name()
+            .ok_or_else(|| anyhow!("Config path '{}' has no file name", self.config_path.to_string_lossy()))?;
+        Ok(vec![config_file.into(), self.config.package_dir.clone()])
+    }
+
+    // fn parse_package_configuration(&self) -> anyhow::Result<PackageConfiguration> {
+    //     let absolute_package_dir = self.absolute_package_dir()?;
+    //     PackageConfiguration::parse(&absolute_package_dir).context("failed to parse package configuration")
+    // }
+
+    fn write_config_to_disk(&self) -> anyhow::Result<()> {
+        Ok(write_config(&self.config_path, &self.config)?)
+    }
+}
+
+fn write_config(config_path: &Path, config: &Config) -> crate::Result<()> {
+    log::debug!("Writing config file '{}'", config_path.to_string_lossy());
+    let serialized_config = toml::to_string_pretty(config)?;
+    let mut file = std::fs::File::create(config_path)?;
+    write!(file, "{}", serialized_config)?;
+    Ok(())
+}
+
+
+/// Returns the directory containing the config file at `config_path`, `.` for a bare file name.
+pub fn config_dir(config_path: &Path) -> Option<&Path> {
+    config_path
+        .parent()
+        .map(|dir| if dir.as_os_str().is_empty() { Path::new(".") } else { dir })
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Config {
+    pub package_dir: PathBuf,
+    /// Variables interpolated in the members of package groups, e.g. `kernel = "linux"` for `"${kernel}-headers"`
+    #[serde(default, skip_serializing_if="BTreeMap::is_empty")]
+    pub variables: Variables,
+    pub targets: BTreeMap<TargetId, TargetConfig>,
+    #[serde(default, skip_serializing_if="GitConfig::is_default")]
+    pub git: GitConfig,
+}
+
+/// Settings for configurations tracked in a git repository
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitConfig {
+    /// Commit the changes of every command modifying the configuration
+    #[serde(default)]
+    pub auto_commit: bool,
+}
+
+impl GitConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Config {
+    /// Returns the variables of a target: the global variables, overridden by the target's own.
+    pub fn target_variables(&self, target: &TargetConfig) -> Variables {
+        let mut variables = self.variables.clone();
+        variables.extend(target.variables.iter().map(|(name, value)| (name.clone(), value.clone())));
+        variables
+    }
+
+    /// Returns an iterator over the targets that have `group_id` as root group.
+    pub fn targets_with_root_group<'a>(&'a self, group_id: &'a GroupId) -> impl Iterator<Item = &'a TargetId> {
+        self.targets
+            .iter()
+            .filter(|(_, target_config)| target_config.root_groups.contains(group_id))
+            .map(|(target_id, _)| target_id)
+    }
+
+    /// Creates an instance of `Self` with placeholder values to template the config file.
+    /// 
+    /// Reads some system-specific data for user-friendly initial values:
+    /// * `/etc/hostname` as default target name
+    /// 
+    /// Will return `Err` if retrieval of system-specific data fails
+    pub fn template() -> anyhow::Result<Self> {
+        let mut targets = BTreeMap::new();
+
+        let hostname = std::fs::read_to_string("/etc/hostname")?;
+        targets.insert(
+            hostname,
+            TargetConfig::from_root_groups([String::from("awesome_software")].into())
+        );
+        Ok(Self {
+                    package_dir : "./packages".into(),
+                    variables: Variables::new(),
+                    targets,
+                    git: GitConfig::default(),
+                })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TargetConfig {
+    pub root_groups: BTreeSet<GroupId>,
+    /// Variables overriding the global variables for this target
+    #[serde(default, skip_serializing_if="BTreeMap::is_empty")]
+    pub variables: Variables,
+}
+
+impl TargetConfig {
+    pub fn from_root_groups(root_groups: BTreeSet<GroupId>) -> Self {
+        Self { root_groups, variables: Variables::new() }
+    }
+}
+
+/// Represents the parsed form of the entire package configuration of a system.
+/// Files are indexed by their absolute paths.
+#[derive(Debug, Default, Clone)]
+pub struct PackageConfiguration {
+    pub files : HashMap<PathBuf, PackageFile>
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PackageFile {
+    pub groups: PackageGroupMap
+}
+
+impl PackageFile {
+    pub fn new() -> Self {
+        Self {
+            groups: BTreeMap::new()
+        }
+    }
+    pub fn from_groups(groups: PackageGroupMap) -> Self {
+        Self { groups }
+    }
+}
+
+impl PackageConfiguration{
+    /// Returns a copy of the configuration with the variables interpolated in the members of `groups`.
+    /// Members of other groups are interpolated where possible and kept as they are if they reference undefined variables,
+    /// as those may only be defined for other targets.
+    pub fn interpolated(&self, groups: &BTreeSet<GroupId>, variables: &Variables) -> crate::Result<Self> {
+        let mut interpolated = self.clone();
+        for (group_id, group) in interpolated.files.values_mut().flat_map(|file| file.groups.iter_mut()) {
+            if !groups.contains(group_id) {
+                group.members = group.members
+                    .iter()
+                    .map(|member| variables::interpolate(member, variables).unwrap_or_else(|_| member.clone()))
+                    .collect();
+                continue;
+            }
+            group.members = group.members
+                .iter()
+                .map(|member| variables::interpolate(member, variables).map_err(|error| match error {
+                    InterpolationError::Undefined(undefined) => Error::UndefinedVariable { variable: undefined[0].clone(), group: group_id.clone() },
+                    InterpolationError::InvalidName(interpolated) => Error::InvalidInterpolation { member: member.clone(), interpolated, group: group_id.clone() },
+                }))
+                .collect::<crate::Result<_>>()?;
+        }
+        Ok(interpolated)
+    }
+
+    /// Parses a package directory to generate a corresponding `PackageConfiguration`
+    fn parse(package_dir: &Path) -> crate::Result<Self> {
+        let mut package_configuration = PackageConfiguration::default();
+        let mut skipped_files = Vec::new();
+
+        for entry in WalkDir::new(package_dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| !e.file_type().is_dir()) {
+            
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "toml") {
+                skipped_files.push(path.to_owned());
+                continue;
+            }
+            log::trace!("Parsing package file '{}'", path.to_string_lossy());
+            let file_string = std::fs::read_to_string(path)?;
+            let groups: PackageGroupMap = toml::from_str(&file_string)?;
+            log::trace!("Parsed groups {:?} from '{}'", groups.keys().collect::<Vec<_>>(), path.to_string_lossy());
+
+            if let Some(_duplicate_file) = package_configuration.files.insert(path.to_owned(), PackageFile { groups }) {
+                return Err(Error::PackageFileAlreadyExists { package_file: path.to_path_buf() })
+            }
+        }
+
+        // hook scripts are stored next to the package files, other files are likely package files missing the extension
+        let hook_scripts : BTreeSet<PathBuf> = package_configuration.iter_groups()
+            .flat_map(|(_, group)| group.post_install.iter().chain(&group.pre_remove))
+            .map(|script| package_dir.join(script))
+            .collect();
+        for path in skipped_files {
+            if hook_scripts.contains(&path) {
+                log::trace!("Skipping hook script '{}'", path.to_string_lossy());
+            } else {
+                log::warn!("Skipping '{}', package files need the `toml` extension", path.to_string_lossy());
+            }
+        }
+        Ok(package_configuration)
+    }
+
+    /// Creates a new group in the package file at the specified `file_path`.
+    /// Returns Err if the file does not exist or the group already exists in any file
+    pub fn create_group(&mut self, group_id: GroupId, file_path: &Path) -> crate::Result<()> {
+        self.insert_group(group_id, PackageGroup::new(), file_path)
+    }
+
+    /// Returns the path of the file containing the group and the group itself.
+    pub fn find_group(&self, group_id: &GroupId) -> Option<(&PathBuf, &PackageGroup)> {
+        self.files
+            .iter()
+            .find_map(|(file_path, package_file)| package_file.groups.get(group_id).map(|group| (file_path, group)))
+    }
+
+    /// Deletes a group from the file containing it and returns the deleted group.
+    pub fn delete_group(&mut self, group_id: &GroupId) -> crate::Result<PackageGroup> {
+        let Some((file_path, group)) = self.files
+            .iter_mut()
+            .find_map(|(file_path, package_file)| package_file.groups.remove(group_id).map(|group| (file_path.clone(), group))) else {
+                return Err(Error::GroupNotFound { group: group_id.clone() });
+            };
+        self.write_file_to_disk(&file_path)?;
+        Ok(group)
+    }
+
+    /// Renames a group, keeping it in the file that contains it.
+    ///
+    /// The rename is applied as a transaction: if any step fails, all changes are rolled back.
+    pub fn rename_group(&mut self, group_id: &GroupId, new_group_id: GroupId) -> crate::Result<()> {
+        if self.find_group(&new_group_id).is_some() {
+            return Err(Error::GroupAlreadyExists { group: new_group_id });
+        }
+        let Some((file_path, _)) = self.find_group(group_id) else {
+            return Err(Error::GroupNotFound { group: group_id.clone() });
+        };
+        let file_path = file_path.clone();
+        self.transaction(|package_config| {
+            let group = package_config.delete_group(group_id)?;
+            package_config.insert_group(new_group_id, group, &file_path)
+        })
+    }
+
+    /// Moves a group to the package file at `file_path`, which has to exist.
+    ///
+    /// The move is applied as a transaction: if any step fails, all changes are rolled back.
+    pub fn move_group(&mut self, group_id: &GroupId, file_path: &Path) -> crate::Result<()> {
+        if !self.files.contains_key(file_path) {
+            return Err(Error::PackageFileNotFound { package_file: file_path.to_owned() });
+        }
+        self.transaction(|package_config| {
+            let group = package_config.delete_group(group_id)?;
+            package_config.insert_group(group_id.clone(), group, file_path)
+        })
+    }
+
+    /// Inserts a group into the package file at `file_path` and writes the file to disk.
+    /// Returns Err if the file does not exist or the group already exists in any file
+    fn insert_group(&mut self, group_id: GroupId, group: PackageGroup, file_path: &Path) -> crate::Result<()> {
+        if self.find_group(&group_id).is_some() {
+            return Err(Error::GroupAlreadyExists { group: group_id });
+        }
+        let Some(package_file) = self.files.get_mut(file_path) else {
+            return Err(Error::PackageFileNotFound{ package_file: file_path.to_owned()})
+        };
+        package_file.groups.insert(group_id, group);
+        self.write_file_to_disk(file_path)
+    }
+
+    /// Returns an iterator over the packages contained by the specified groups.
+    pub fn packages_of_groups<'a>(&'a self, groups: &'a BTreeSet<GroupId>) -> impl Iterator<Item = &'a PackageId> + 'a  {
+        self.filter_groups(groups)
+            .flat_map(|(_, package_group)| &package_group.members)
+    }
+
+    /// Creates an iterator over package groups pre-filtered to only contain the specified groups.
+    pub fn filter_groups<'a>(&'a self, groups: &'a BTreeSet<GroupId>) -> impl Iterator<Item = (&'a GroupId, &'a PackageGroup)> {
+        self.files
+            .values()
+            .flat_map(|contents| &contents.groups)
+            .filter(|(group_name, _)| groups.contains(*group_name))
+    }
+
+    /// Creates a new package configuration file at the specified path.
+    /// If no contents are passed, the file will be created empty.
+    // TODO: check that supplied path is inside package directory (or use special wrapper type (`PathInsidePackageDir`) that makes that guarantee?)
+    pub fn create_file(&mut self, file_path_abs: &Path, opt_contents: Option<PackageGroupMap> ) -> crate::Result<()>{
+        
+        if !self.files.contains_key(file_path_abs) {
+
+            let package_file = match opt_contents {
+                Some(groups) => PackageFile::from_groups(groups),
+                None => PackageFile::new(),
+            };
+
+            self.files.insert(file_path_abs.to_owned(), package_file);
+            self.write_file_to_disk(file_path_abs)?;
+            Ok(())
+        } else {
+            Err(Error::PackageFileAlreadyExists { package_file: file_path_abs.to_owned() })
+        }
+    }
+
+    /// Replaces the groups of the existing package file at the specified path.
+    /// Returns Err if any of the groups already exists in another file
+    pub fn update_file(&mut self, file_path: &Path, groups: PackageGroupMap) -> crate::Result<()> {
+        if !self.files.contains_key(file_path) {
+            return Err(Error::PackageFileNotFound { package_file: file_path.to_owned() });
+        }
+        if let Some(duplicate_group) = self.files
+            .iter()
+            .filter(|(other_file_path, _)| *other_file_path != file_path)
+            .flat_map(|(_, package_file)| package_file.groups.keys())
+            .find(|group_id| groups.contains_key(*group_id)) {
+                return Err(Error::GroupAlreadyExists { group: duplicate_group.clone() });
+            }
+
+        self.files.insert(file_path.to_owned(), PackageFile::from_groups(groups));
+        self.write_file_to_disk(file_path)
+    }
+
+    pub fn iter_groups(&self) -> impl Iterator<Item = (&GroupId, &PackageGroup)> {
+        self.files.values()
+            .flat_map(|contents| &contents.groups)
+    }
+
+    /// Adds packages to the specified group
+    pub fn add_packages<I>(&mut self, packages: I, group_id: &GroupId) -> crate::Result<()>
+    where
+        I: IntoIterator<Item = String>
+    {
+        let mut package_group_ref : Option<&mut BTreeSet<PackageId>> = Option::None;
+        let mut file_to_save : Option<PathBuf> = Option::None;
+
+        'outer: for (filepath, package_file) in self.files.iter_mut() {
+            for (group_name, g) in package_file.groups.iter_mut() {
+                if group_name == group_id {
+                    package_group_ref = Some(&mut g.members);
+                    file_to_save = Some(filepath.clone());
+                    break 'outer;
+                }
+            }
+        }
+        let (Some(package_group), Some(file)) = (package_group_ref, file_to_save) else {
+            return Err(Error::GroupNotFound { group: group_id.clone() });
+        };
+        package_group.extend(packages);
+        self.write_file_to_disk(&file)?;
+        Ok(())
+    }
+
+    /// Removes a package from a group.
+    pub fn remove_package(&mut self, package_id: &PackageId, group_id: &GroupId) -> crate::Result<()> {
+        let mut file_to_save : Option<PathBuf> = Option::None;
+        let mut group_found = false;
+
+        'outer: for (filepath, package_file) in self.files.iter_mut() {
+            for (group_name, g) in package_file.groups.iter_mut() {
+                if group_name == group_id {
+                    group_found = true;
+                    if g.members.remove(package_id) {
+                        file_to_save = Some(filepath.clone());
+                    }
+                    break 'outer;
+                }
+            }
+        }
+        let Some(file) = file_to_save else {
+            if !group_found {
+                return Err(Error::GroupNotFound { group: group_id.to_owned() });
+            } else {
+                return Err(Error::PackageNotFound { package: package_id.clone() });
+            }
+        };
+        self.write_file_to_disk(&file)?;
+        Ok(())
+    }
+
+    /// Moves packages from one group to another.
+    /// If no packages are passed, all members of `from_group` are moved. Returns the moved packages.
+    ///
+    /// The move is applied as a transaction: if any step fails, all changes are rolled back.
+    pub fn move_packages(&mut self, packages: Option<BTreeSet<PackageId>>, from_group: &GroupId, to_group: &GroupId) -> crate::Result<BTreeSet<PackageId>> {
+        let packages = match packages {
+            Some(packages) => packages,
+            None => self.find_group(from_group)
+                .ok_or_else(|| Error::GroupNotFound { group: from_group.clone() })?
+                .1.members.clone(),
+        };
+        if self.find_group(to_group).is_none() {
+            return Err(Error::GroupNotFound { group: to_group.clone() });
+        }
+
+        self.transaction(|package_config| {
+            for package in &packages {
+                package_config.remove_package(package, from_group)?;
+            }
+            package_config.add_packages(packages.iter().cloned(), to_group)?;
+            Ok(())
+        })?;
+        Ok(packages)
+    }
+
+    /// Applies the changes performed by `f` as a transaction.
+    /// If `f` returns `Err`, the package configuration is restored to its previous state, both in memory and on disk:
+    /// changed and removed files are rewritten, files created by `f` are deleted.
+    pub fn transaction<F, T>(&mut self, f: F) -> crate::Result<T>
+    where
+        F: FnOnce(&mut Self) -> crate::Result<T>
+    {
+        let snapshot = self.files.clone();
+        match f(self) {
+            Ok(value) => Ok(value),
+            Err(error) => {
+                log::warn!("Rolling back package configuration: {}", error);
+                let changed_files : BTreeSet<PathBuf> = self.files
+                    .iter()
+                    .filter(|(file_path, package_file)| snapshot.get(*file_path) != Some(*package_file))
+                    .map(|(file_path, _)| file_path.clone())
+                    .chain(snapshot.keys().filter(|file_path| !self.files.contains_key(*file_path)).cloned())
+                    .collect();
+                self.files = snapshot;
+
+                // every file is restored even if restoring another one failed, the first failure is reported
+                let rollback_errors : Vec<Error> = changed_files.iter().filter_map(|file_path| {
+                    if self.files.contains_key(file_path) {
+                        self.write_file_to_disk(file_path).err()
+                    } else {
+                        // the file was created by the transaction
+                        fs::remove_file(file_path).err().map(Error::from)
+                    }
+                }).collect();
+                match rollback_errors.into_iter().next() {
+                    None => Err(error),
+                    Some(rollback_error) => Err(Error::RollbackFailed { error: Box::new(error), source: Box::new(rollback_error) }),
+                }
+            }
+        }
+    }
+
+    // Write a file with its currently configured groups to disk.
+    // Will create the file if it exists or truncate otherwise.
+    fn write_file_to_disk(&self, file_path: &Path) -> crate::Result<()> {
+        let Some(group_file) = self.files.get(file_path) else {
+            return Err(Error::PackageFileNotFound { package_file: file_path.to_owned() });
+        };
+        log::debug!("Writing package file '{}'", file_path.to_string_lossy());
+        let serialized_groups = toml::to_string_pretty(&group_file.groups)?;
+        let mut file = std::fs::File::create(file_path)?;
+        write!(file, "{}", serialized_groups)?;
+        Ok(())
+    }
+    
+}
+pub type TargetId = String;
+
+// Manages the active Target of the system. Will write to the active target file on configuration change
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActiveTarget {
+    target : TargetId,
+}
+
+impl ActiveTarget {
+    pub fn new(target: TargetId) -> Self {
+        Self {target}
+    }
+
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let active_target: Self = toml::from_str(s)?;
+        Ok(active_target)
+    }
+
+    pub fn target(&self) -> &TargetId {
+        &self.target
+    }
+
+    // TODO(medium): nicer interface, not so intuitive having to pass the path here. Same approach as ConfigManager?
+    /// Changes the active target and writes it to the specified file
+    pub fn set_target(&mut self, target: TargetId, path: &Path, ) -> Result<(), Error> {
+        self.target = target;
+        let serialized_target = toml::to_string_pretty(self)?;
+        let mut file = std::fs::File::create(path)?;
+        write!(file, "{}", serialized_target)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_management() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let base_file = dir.join("base.toml");
+        let dev_file = dir.join("dev.toml");
+
+        let mut package_config = PackageConfiguration::default();
+        package_config.create_file(&base_file, None).unwrap();
+        package_config.create_file(&dev_file, None).unwrap();
+
+        package_config.create_group("tools".to_owned(), &base_file).unwrap();
+        package_config.add_packages(["bat".to_owned()], &"tools".to_owned()).unwrap();
+        assert!(matches!(package_config.create_group("tools".to_owned(), &dev_file), Err(Error::GroupAlreadyExists { .. })));
+
+        package_config.rename_group(&"tools".to_owned(), "cli-tools".to_owned()).unwrap();
+        assert!(package_config.find_group(&"tools".to_owned()).is_none());
+
+        package_config.move_group(&"cli-tools".to_owned(), &dev_file).unwrap();
+        let (file_path, group) = package_config.find_group(&"cli-tools".to_owned()).unwrap();
+        assert_eq!(&dev_file, file_path);
+        assert!(group.members.contains("bat"));
+
+        // changes are persisted
+        let reparsed = PackageConfiguration::parse(dir).unwrap();
+        assert_eq!(package_config.find_group(&"cli-tools".to_owned()), reparsed.find_group(&"cli-tools".to_owned()));
+
+        package_config.delete_group(&"cli-tools".to_owned()).unwrap();
+        assert!(matches!(package_config.delete_group(&"cli-tools".to_owned()), Err(Error::GroupNotFound { .. })));
+    }
+
+    #[test]
+    fn test_interpolated() {
+        let package_config = crate::test_util::package_config(&[("kernel", &["${kernel}-headers"]), ("gpu", &["${gpu}", "${kernel}-docs"])]);
+        let variables : Variables = [("kernel".to_owned(), "linux-lts".to_owned())].into();
+
+        let interpolated = package_config.interpolated(&["kernel".to_owned()].into(), &variables).unwrap();
+        assert_eq!(&crate::test_util::set(&["linux-lts-headers"]), &interpolated.find_group(&"kernel".to_owned()).unwrap().1.members);
+        // other groups keep members referencing undefined variables
+        assert_eq!(&crate::test_util::set(&["${gpu}", "linux-lts-docs"]), &interpolated.find_group(&"gpu".to_owned()).unwrap().1.members);
+
+        assert!(matches!(package_config.interpolated(&["gpu".to_owned()].into(), &variables), Err(Error::UndefinedVariable { .. })));
+        let variables : Variables = [("kernel".to_owned(), "-linux".to_owned())].into();
+        assert!(matches!(package_config.interpolated(&["kernel".to_owned()].into(), &variables), Err(Error::InvalidInterpolation { .. })));
+    }
+
+    #[test]
+    fn test_target_management() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let config_path = dir.join("config.toml");
+        fs::create_dir(dir.join("packages")).unwrap();
+        fs::write(dir.join("packages/base.toml"), "[base]\nmembers = [\"bash\"]\n\n[dev]\nmembers = [\"git\"]\n").unwrap();
+        // the comment is dropped whenever the config file is rewritten
+        fs::write(&config_path, "# untouched\npackage_dir = \"packages\"\n\n[targets.laptop]\nroot_groups = [\"base\"]\n").unwrap();
+        let mut config_manager = ConfigManager::parse(config_path.clone()).unwrap();
+
+        // unchanged targets are not written
+        assert!(!config_manager.add_root_group(&"laptop".to_owned(), "base".to_owned()).unwrap());
+        assert!(!config_manager.remove_root_group(&"laptop".to_owned(), &"dev".to_owned()).unwrap());
+        assert!(fs::read_to_string(&config_path).unwrap().starts_with("# untouched"));
+
+        assert!(config_manager.add_root_group(&"laptop".to_owned(), "dev".to_owned()).is_ok_and(|added| added));
+        assert!(config_manager.add_root_group(&"laptop".to_owned(), "nope".to_owned()).is_err());
+        assert!(config_manager.add_root_group(&"desktop".to_owned(), "dev".to_owned()).is_err());
+
+        config_manager.copy_target(&"laptop".to_owned(), "desktop".to_owned()).unwrap();
+        assert!(config_manager.copy_target(&"laptop".to_owned(), "desktop".to_owned()).is_err());
+        config_manager.rename_target(&"desktop".to_owned(), "workstation".to_owned()).unwrap();
+        assert!(config_manager.rename_target(&"desktop".to_owned(), "server".to_owned()).is_err());
+        assert!(config_manager.remove_root_group(&"workstation".to_owned(), &"base".to_owned()).unwrap());
+        assert!(config_manager.create_target("server".to_owned(), TargetConfig::from_root_groups(["nope".to_owned()].into())).is_err());
+        config_manager.create_target("server".to_owned(), TargetConfig::from_root_groups(["base".to_owned()].into())).unwrap();
+        config_manager.delete_target(&"laptop".to_owned()).unwrap();
+        assert!(config_manager.delete_target(&"laptop".to_owned()).is_err());
+
+        // changes are persisted
+        let reparsed = ConfigManager::parse(config_path).unwrap();
+        let root_groups : Vec<(&TargetId, Vec<&GroupId>)> = reparsed.config().targets.iter()
+            .map(|(target_id, target_config)| (target_id, target_config.root_groups.iter().collect()))
+            .collect();
+        assert_eq!(vec![(&"server".to_owned(), vec![&"base".to_owned()]), (&"workstation".to_owned(), vec![&"dev".to_owned()])], root_groups);
+    }
+
+    #[test]
+    fn test_tracked_paths_of_relative_config() {
+        // relative to the working directory of the test, the package root
+        let temp_dir = tempfile::tempdir_in("target").unwrap();
+        let dir = temp_dir.path();
+        fs::create_dir(dir.join("packages")).unwrap();
+        fs::write(dir.join("packages/base.toml"), "[base]\nmembers = []\n").unwrap();
+        fs::write(dir.join("config.toml"), "package_dir = \"./packages\"\n\n[targets]\n").unwrap();
+
+        let config_manager = ConfigManager::parse(dir.join("config.toml")).unwrap();
+        assert_eq!(dir, config_manager.config_dir().unwrap());
+        assert_eq!(vec![PathBuf::from("config.toml"), PathBuf::from("./packages")], config_manager.tracked_paths().unwrap());
+        assert_eq!(Some(Path::new(".")), config_dir(Path::new("config.toml")));
+
+        // the paths are valid in a repository of the config directory
+        for args in [&["init", "--quiet"][..], &["add", "--dry-run", "--", "config.toml", "./packages"]] {
+            let status = std::process::Command::new("git").arg("-C").arg(dir).args(args).stdout(std::process::Stdio::null()).status().unwrap();
+            assert!(status.success());
+        }
+    }
+
+    #[test]
+    fn test_config_write_rollback() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let config_path = dir.join("config.toml");
+        let base_file = dir.join("packages/base.toml");
+        fs::create_dir(dir.join("packages")).unwrap();
+        fs::write(&base_file, "[base]\nmembers = [\"bash\"]\n").unwrap();
+        fs::write(&config_path, "package_dir = \"packages\"\n\n[targets.laptop]\nroot_groups = [\"base\"]\n").unwrap();
+        let mut config_manager = ConfigManager::parse(config_path.clone()).unwrap();
+
+        // writing the config fails after the package file was written
+        fs::remove_file(&config_path).unwrap();
+        fs::create_dir(&config_path).unwrap();
+        assert!(config_manager.rename_group(&"base".to_owned(), "core".to_owned()).is_err());
+        assert!(config_manager.delete_group(&"base".to_owned()).is_err());
+
+        let base_groups : PackageGroupMap = toml::from_str(&fs::read_to_string(&base_file).unwrap()).unwrap();
+        assert!(base_groups.contains_key("base"));
+        assert!(config_manager.package_config().find_group(&"base".to_owned()).is_some());
+        assert!(config_manager.config().targets["laptop"].root_groups.contains("base"));
+    }
+
+    #[test]
+    fn test_move_group_rollback() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let base_file = dir.join("base.toml");
+        let dev_file = dir.join("dev.toml");
+
+        let mut package_config = PackageConfiguration::default();
+        package_config.create_file(&base_file, None).unwrap();
+        package_config.create_file(&dev_file, None).unwrap();
+        package_config.create_group("tools".to_owned(), &base_file).unwrap();
+        package_config.add_packages(["bat".to_owned()], &"tools".to_owned()).unwrap();
+
+        // writing the destination fails after the group was removed from the source file,
+        // restoring the destination fails as well, but the source is still restored
+        fs::remove_file(&dev_file).unwrap();
+        fs::create_dir(&dev_file).unwrap();
+        assert!(matches!(package_config.move_group(&"tools".to_owned(), &dev_file), Err(Error::RollbackFailed { .. })));
+
+        assert_eq!(&base_file, package_config.find_group(&"tools".to_owned()).unwrap().0);
+        let base_groups : PackageGroupMap = toml::from_str(&fs::read_to_string(&base_file).unwrap()).unwrap();
+        assert!(base_groups["tools"].members.contains("bat"));
+    }
+
+    #[test]
+    fn test_transaction_file_set() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let base_file = dir.join("base.toml");
+        let dev_file = dir.join("dev.toml");
+        let new_file = dir.join("new.toml");
+
+        let mut package_config = PackageConfiguration::default();
+        package_config.create_file(&base_file, None).unwrap();
+        package_config.create_file(&dev_file, None).unwrap();
+        package_config.create_group("tools".to_owned(), &dev_file).unwrap();
+
+        let result : crate::Result<()> = package_config.transaction(|package_config| {
+            package_config.files.remove(&dev_file);
+            fs::remove_file(&dev_file)?;
+            package_config.create_file(&new_file, None)?;
+            Err(Error::GroupNotFound { group: "nope".to_owned() })
+        });
+        assert!(matches!(result, Err(Error::GroupNotFound { .. })));
+
+        assert!(!new_file.exists());
+        let reparsed = PackageConfiguration::parse(dir).unwrap();
+        for package_config in [&package_config, &reparsed] {
+            let mut files : Vec<&PathBuf> = package_config.files.keys().collect();
+            files.sort();
+            assert_eq!(vec![&base_file, &dev_file], files);
+            assert_eq!(&dev_file, package_config.find_group(&"tools".to_owned()).unwrap().0);
+        }
+    }
+
+    #[test]
+    fn test_move_packages_rollback() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let base_file = dir.join("base.toml");
+        let dev_file = dir.join("dev.toml");
+
+        let mut package_config = PackageConfiguration::default();
+        package_config.create_file(&base_file, None).unwrap();
+        package_config.create_file(&dev_file, None).unwrap();
+        package_config.create_group("tools".to_owned(), &base_file).unwrap();
+        package_config.create_group("dev".to_owned(), &dev_file).unwrap();
+        package_config.add_packages(["bat".to_owned(), "htop".to_owned()], &"tools".to_owned()).unwrap();
+
+        // moving a package that is not in the group fails after `bat` has already been removed
+        let packages : BTreeSet<PackageId> = ["bat".to_owned(), "nope".to_owned()].into();
+        let result = package_config.move_packages(Some(packages), &"tools".to_owned(), &"dev".to_owned());
+        assert!(matches!(result, Err(Error::PackageNotFound { .. })));
+
+        let reparsed = PackageConfiguration::parse(dir).unwrap();
+        for package_config in [&package_config, &reparsed] {
+            assert_eq!(2, package_config.find_group(&"tools".to_owned()).unwrap().1.members.len());
+            assert!(package_config.find_group(&"dev".to_owned()).unwrap().1.members.is_empty());
+        }
+
+        let moved = package_config.move_packages(None, &"tools".to_owned(), &"dev".to_owned()).unwrap();
+        assert_eq!(2, moved.len());
+        let reparsed = PackageConfiguration::parse(dir).unwrap();
+        assert!(reparsed.find_group(&"tools".to_owned()).unwrap().1.members.is_empty());
+        assert_eq!(moved, reparsed.find_group(&"dev".to_owned()).unwrap().1.members);
+    }
+}
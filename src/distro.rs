@@ -0,0 +1,324 @@
+use std::{collections::BTreeSet, fmt::Display, path::Path};
+
+use anyhow::{Context, bail};
+use os_release::OsRelease;
+
+use crate::{PackageGroupMap, PackageGroup, PackageId};
+use source::TemplateSource;
+
+/// Sources to read the package lists of providers from
+pub mod source;
+/// Detection of the desktop environment
+pub mod desktop;
+
+mod arch;
+mod cachyos;
+mod endeavouros;
+mod garuda;
+mod manjaro;
+
+/// A distro supported for templating the package configuration.
+pub trait DistroProvider {
+    /// Unique identifier of the provider, used to select it on the command line
+    fn id(&self) -> &'static str;
+
+    /// Human-readable name of the distro, also used to name the templated package file and groups
+    fn name(&self) -> &'static str;
+
+    /// Values of `ID` in `/etc/os-release` identifying the distro
+    fn os_release_ids(&self) -> &'static [&'static str];
+
+    /// Identifiers of the desktops the distro provides package lists for
+    fn desktops(&self) -> &'static [&'static str];
+
+    /// Maps a desktop name, e.g. from `XDG_CURRENT_DESKTOP` or a session file, to one of `desktops`.
+    /// Returns `None` if the distro provides no package list for the desktop.
+    fn desktop_id(&self, desktop: &str) -> Option<&'static str> {
+        let desktop = desktop::normalize_desktop(desktop);
+        self.desktops().iter().find(|id| **id == desktop).copied()
+    }
+
+    /// Generates the template package groups for the system configuration, reading package lists from `source`
+    fn generate_groups(&self, system_config: &SystemConfiguration, source: &TemplateSource) -> anyhow::Result<PackageGroupMap>;
+}
+
+/// Registry of the available distro providers.
+pub struct DistroRegistry {
+    providers: Vec<Box<dyn DistroProvider>>,
+}
+
+impl Default for DistroRegistry {
+    /// Creates a registry containing all built-in providers
+    fn default() -> Self {
+        Self {
+            providers: vec![
+                Box::new(arch::Arch),
+                Box::new(endeavouros::EndeavourOs),
+                Box::new(manjaro::Manjaro),
+                Box::new(cachyos::CachyOs),
+                Box::new(garuda::Garuda),
+            ]
+        }
+    }
+}
+
+impl DistroRegistry {
+    pub fn providers(&self) -> impl Iterator<Item = &dyn DistroProvider> {
+        self.providers.iter().map(|provider| provider.as_ref())
+    }
+
+    /// Returns the provider with the specified id
+    pub fn get(&self, id: &str) -> Option<&dyn DistroProvider> {
+        self.providers().find(|provider| provider.id() == id)
+    }
+
+    /// Finds the provider for the distro described by `os_release`.
+    /// Providers matching `ID` take precedence over providers matching any of `ID_LIKE`, in the order of `ID_LIKE`.
+    pub fn detect(&self, os_release: &OsRelease) -> Option<&dyn DistroProvider> {
+        let find_by_id = |id: &str| self.providers().find(|provider| provider.os_release_ids().contains(&id));
+
+        find_by_id(&os_release.id)
+            .or_else(|| os_release.id_like.split_whitespace().find_map(find_by_id))
+    }
+
+    /// Finds the provider for the running system.
+    pub fn detect_system(&self) -> anyhow::Result<&dyn DistroProvider> {
+        let os_release = OsRelease::new().context("Failed to read /etc/os-release")?;
+        log::debug!("Detecting distro provider for ID '{}', ID_LIKE '{}'", os_release.id, os_release.id_like);
+        self.detect(&os_release)
+            .with_context(|| format!("Distro not supported for package templating: {}", &os_release.name))
+    }
+}
+
+/// Gets the system configuration to template for the distro of `provider`.
+/// Uses `desktop` if passed, otherwise detects the desktop, which is `None` if no desktop supported by the provider is found.
+pub fn get_system_configuration(provider: &dyn DistroProvider, desktop: Option<&str>) -> anyhow::Result<SystemConfiguration> {
+    let desktop = match desktop {
+        Some(desktop) => {
+            let Some(desktop_id) = provider.desktop_id(desktop) else {
+                bail!("{} provides no package list for desktop '{}'. Available desktops: {}", provider.name(), desktop, provider.desktops().join(", "))
+            };
+            Some(desktop_id.to_owned())
+        },
+        None => {
+            let candidates = desktop::desktop_candidates(Path::new("/"));
+            log::debug!("Desktop candidates: {:?}", candidates);
+            candidates
+                .iter()
+                .find_map(|candidate| provider.desktop_id(candidate))
+                .map(|desktop_id| desktop_id.to_owned())
+        },
+    };
+    log::debug!("Templating desktop {:?} of {}", desktop, provider.name());
+    Ok(SystemConfiguration {
+        distro: provider.name().to_owned(),
+        desktop
+    })
+}
+
+/// Fetches the package list at `path` relative to `base_url` from `source`, parsing it with `parse`.
+fn fetch_package_list(source: &TemplateSource, base_url: &str, path: &str, parse: fn(&str) -> BTreeSet<PackageId>) -> anyhow::Result<BTreeSet<PackageId>> {
+    let list = source.fetch(base_url, path)?;
+    let packages = parse(&list);
+    log::trace!("Parsed {} packages from '{}'", packages.len(), path);
+    Ok(packages)
+}
+
+/// Parses a package list containing one package per line. Empty lines and `#` comments are skipped.
+fn parse_plain_list(list: &str) -> BTreeSet<PackageId> {
+    list.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_owned())
+        .collect()
+}
+
+/// Parses a package list in the format used by manjaro-tools ISO profiles.
+/// Lines are prefixed with markers like `>extra` or `>multilib`, and `KERNEL` is a placeholder for the selected kernel.
+/// Packages restricted to `i686` and kernel-specific packages are skipped.
+fn parse_manjaro_tools_list(list: &str) -> BTreeSet<PackageId> {
+    list.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter_map(|line| match line.strip_prefix('>') {
+            Some(marked_line) => match marked_line.split_once(char::is_whitespace) {
+                Some((marker, package)) if marker != "i686" => Some(package.trim()),
+                _ => None,
+            },
+            None => Some(line),
+        })
+        .filter(|package| !package.is_empty() && !package.contains("KERNEL"))
+        .map(|package| package.to_owned())
+        .collect()
+}
+
+/// How to merge freshly templated groups into previously templated ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum MergeStrategy {
+    /// Keep the existing group
+    Keep,
+    /// Replace the existing group with the templated group
+    Replace,
+    /// Keep the packages of both groups
+    Union,
+}
+
+/// Difference of a templated group to the existing group of the same name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupDiff {
+    /// Packages in the templated group, but not in the existing group
+    pub added: BTreeSet<PackageId>,
+    /// Packages in the existing group, but not in the templated group
+    pub removed: BTreeSet<PackageId>,
+}
+
+impl GroupDiff {
+    pub fn new(existing: &PackageGroup, templated: &PackageGroup) -> Self {
+        Self {
+            added: templated.members.difference(&existing.members).cloned().collect(),
+            removed: existing.members.difference(&templated.members).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Merges a templated group into the existing group according to `strategy`.
+pub fn merge_group(existing: &PackageGroup, templated: &PackageGroup, strategy: MergeStrategy) -> PackageGroup {
+    match strategy {
+        MergeStrategy::Keep => existing.clone(),
+        MergeStrategy::Replace => templated.clone(),
+        MergeStrategy::Union => PackageGroup {
+            members: existing.members.union(&templated.members).cloned().collect(),
+            services: existing.services.union(&templated.services).cloned().collect(),
+            user_services: existing.user_services.union(&templated.user_services).cloned().collect(),
+            post_install: existing.post_install.clone().or_else(|| templated.post_install.clone()),
+            pre_remove: existing.pre_remove.clone().or_else(|| templated.pre_remove.clone()),
+        },
+    }
+}
+
+/// Comparison of the installed packages to the templated groups of a distro
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateDiff {
+    /// Templated packages that are installed
+    pub kept: BTreeSet<PackageId>,
+    /// Templated packages that are not installed
+    pub removed: BTreeSet<PackageId>,
+    /// Installed packages that are not templated
+    pub added: BTreeSet<PackageId>,
+}
+
+impl TemplateDiff {
+    pub fn new(installed: &BTreeSet<PackageId>, templated_groups: &PackageGroupMap) -> Self {
+        let templated : BTreeSet<PackageId> = templated_groups.values().flat_map(|group| group.members.iter().cloned()).collect();
+        Self {
+            kept: templated.intersection(installed).cloned().collect(),
+            removed: templated.difference(installed).cloned().collect(),
+            added: installed.difference(&templated).cloned().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SystemConfiguration {
+    pub distro: String,
+    /// Desktop identifier of the provider, `None` if no desktop should be templated
+    pub desktop: Option<String>,
+}
+
+impl Display for SystemConfiguration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.desktop {
+            Some(desktop) => write!(f, "{} on {}", self.distro, desktop),
+            None => write!(f, "{} without desktop", self.distro),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os_release(id: &str, id_like: &str) -> OsRelease {
+        [format!("ID={}", id), format!("ID_LIKE={}", id_like)].into_iter().collect()
+    }
+
+    #[test]
+    fn test_detect_provider() {
+        let registry = DistroRegistry::default();
+
+        let detected = |id, id_like| registry.detect(&os_release(id, id_like)).map(|provider| provider.id());
+        assert_eq!(Some("endeavouros"), detected("endeavouros", "arch"));
+        assert_eq!(Some("manjaro"), detected("manjaro", "arch"));
+        assert_eq!(Some("arch"), detected("arch", ""));
+        // unknown derivatives fall back to the distro they are based on
+        assert_eq!(Some("arch"), detected("somederivative", "arch"));
+        assert_eq!(None, detected("debian", ""));
+    }
+
+    #[test]
+    fn test_arch_templates_all_desktops() {
+        let arch = arch::Arch;
+        for desktop in arch.desktops() {
+            let system_config = SystemConfiguration { distro: arch.name().to_owned(), desktop: Some(desktop.to_string()) };
+            let groups = arch.generate_groups(&system_config, &TemplateSource::default()).unwrap();
+            assert!(groups.values().all(|group| !group.members.is_empty()));
+        }
+    }
+
+    #[test]
+    fn test_desktop_id() {
+        let registry = DistroRegistry::default();
+        let endeavouros = registry.get("endeavouros").unwrap();
+        assert_eq!(Some("kde"), endeavouros.desktop_id("KDE"));
+        assert_eq!(Some("gnome"), endeavouros.desktop_id("GNOME-Flashback"));
+        assert_eq!(None, endeavouros.desktop_id("openbox"));
+
+        let garuda = registry.get("garuda").unwrap();
+        assert_eq!(Some("dr460nized"), garuda.desktop_id("plasmawayland.desktop"));
+    }
+
+    #[test]
+    fn test_merge_group() {
+        let existing = PackageGroup::from_members(["base".to_owned(), "helix".to_owned()].into());
+        let templated = PackageGroup::from_members(["base".to_owned(), "nano".to_owned()].into());
+
+        let diff = GroupDiff::new(&existing, &templated);
+        assert_eq!(BTreeSet::from(["nano".to_owned()]), diff.added);
+        assert_eq!(BTreeSet::from(["helix".to_owned()]), diff.removed);
+
+        assert_eq!(existing, merge_group(&existing, &templated, MergeStrategy::Keep));
+        assert_eq!(templated, merge_group(&existing, &templated, MergeStrategy::Replace));
+        assert_eq!(3, merge_group(&existing, &templated, MergeStrategy::Union).members.len());
+    }
+
+    #[test]
+    fn test_template_diff() {
+        let mut templated_groups = PackageGroupMap::new();
+        templated_groups.insert("base".to_owned(), PackageGroup::from_members(["base".to_owned(), "nano".to_owned()].into()));
+        templated_groups.insert("kde".to_owned(), PackageGroup::from_members(["plasma-desktop".to_owned()].into()));
+        let installed : BTreeSet<PackageId> = ["base".to_owned(), "plasma-desktop".to_owned(), "helix".to_owned()].into();
+
+        let template_diff = TemplateDiff::new(&installed, &templated_groups);
+        assert_eq!(BTreeSet::from(["base".to_owned(), "plasma-desktop".to_owned()]), template_diff.kept);
+        assert_eq!(BTreeSet::from(["nano".to_owned()]), template_diff.removed);
+        assert_eq!(BTreeSet::from(["helix".to_owned()]), template_diff.added);
+    }
+
+    #[test]
+    fn test_parse_plain_list() {
+        let list = "# comment\nbase\n\nlinux # kernel\n";
+        let expected : BTreeSet<PackageId> = ["base".to_owned(), "linux".to_owned()].into();
+        assert_eq!(expected, parse_plain_list(list));
+    }
+
+    #[test]
+    fn test_parse_manjaro_tools_list() {
+        let list = "## base\nbase\n>extra firefox\n>multilib lib32-mesa\n>i686 lib32-fake\nKERNEL-headers\n";
+        let expected : BTreeSet<PackageId> = ["base".to_owned(), "firefox".to_owned(), "lib32-mesa".to_owned()].into();
+        assert_eq!(expected, parse_manjaro_tools_list(list));
+    }
+}
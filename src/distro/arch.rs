@@ -0,0 +1,49 @@
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+
+use crate::{PackageGroupMap, PackageGroup};
+
+use super::{DistroProvider, SystemConfiguration, TemplateSource};
+
+const BASE_PACKAGES : &[&str] = &["base", "linux", "linux-firmware"];
+
+/// Desktop packages, following the desktop profiles of archinstall
+const DESKTOP_PACKAGES : &[(&str, &[&str])] = &[
+    ("budgie", &["budgie", "lightdm", "lightdm-gtk-greeter"]),
+    ("cinnamon", &["cinnamon", "lightdm", "lightdm-gtk-greeter"]),
+    ("gnome", &["gnome", "gnome-tweaks", "gdm"]),
+    ("i3", &["i3-wm", "i3lock", "i3status", "dmenu", "lightdm", "lightdm-gtk-greeter"]),
+    ("kde", &["plasma-meta", "konsole", "dolphin", "sddm"]),
+    ("lxqt", &["lxqt", "breeze-icons", "sddm"]),
+    ("mate", &["mate", "mate-extra", "lightdm", "lightdm-gtk-greeter"]),
+    ("sway", &["sway", "swaybg", "swaylock", "swayidle", "foot", "waybar"]),
+    ("xfce", &["xfce4", "xfce4-goodies", "lightdm", "lightdm-gtk-greeter"]),
+];
+
+/// Templates vanilla Arch Linux from built-in package lists, so no source is required
+pub struct Arch;
+
+impl DistroProvider for Arch {
+    fn id(&self) -> &'static str { "arch" }
+    fn name(&self) -> &'static str { "Arch" }
+    fn os_release_ids(&self) -> &'static [&'static str] { &["arch"] }
+    fn desktops(&self) -> &'static [&'static str] {
+        &["budgie", "cinnamon", "gnome", "i3", "kde", "lxqt", "mate", "sway", "xfce"]
+    }
+
+    fn generate_groups(&self, system_config: &SystemConfiguration, _source: &TemplateSource) -> anyhow::Result<PackageGroupMap> {
+        let to_group = |packages: &[&str]| PackageGroup::from_members(packages.iter().map(|package| package.to_string()).collect());
+
+        let mut group_map : PackageGroupMap = BTreeMap::new();
+        group_map.insert(format!("{}-base", system_config.distro), to_group(BASE_PACKAGES));
+
+        if let Some(desktop) = &system_config.desktop {
+            let Some((_, desktop_packages)) = DESKTOP_PACKAGES.iter().find(|(id, _)| id == desktop) else {
+                bail!("No package list for desktop '{}'", desktop)
+            };
+            group_map.insert(format!("{}-{}", system_config.distro, desktop), to_group(desktop_packages));
+        }
+        Ok(group_map)
+    }
+}
@@ -0,0 +1,27 @@
+use std::collections::BTreeMap;
+
+use crate::{PackageGroupMap, PackageGroup};
+
+use super::{DistroProvider, SystemConfiguration, TemplateSource, fetch_package_list, parse_plain_list};
+
+const LIVE_ISO_BASE_URL : &str = "https://raw.githubusercontent.com/CachyOS/CachyOS-Live-ISO/master";
+const PACKAGE_LIST : &str = "archiso/packages_desktop.x86_64";
+
+/// Templates from the package list of the CachyOS live ISO.
+/// CachyOS does not publish per-desktop package lists, so only a base group is generated.
+pub struct CachyOs;
+
+impl DistroProvider for CachyOs {
+    fn id(&self) -> &'static str { "cachyos" }
+    fn name(&self) -> &'static str { "CachyOS" }
+    fn os_release_ids(&self) -> &'static [&'static str] { &["cachyos"] }
+    fn desktops(&self) -> &'static [&'static str] { &[] }
+
+    fn generate_groups(&self, system_config: &SystemConfiguration, source: &TemplateSource) -> anyhow::Result<PackageGroupMap> {
+        let members = fetch_package_list(source, LIVE_ISO_BASE_URL, PACKAGE_LIST, parse_plain_list)?;
+
+        let mut group_map : PackageGroupMap = BTreeMap::new();
+        group_map.insert(format!("{}-base", system_config.distro), PackageGroup::from_members(members));
+        Ok(group_map)
+    }
+}
@@ -0,0 +1,135 @@
+use std::{path::Path, fs};
+
+/// Aliases of desktops, mapping names used in sessions and environment variables to common identifiers
+const DESKTOP_ALIASES : &[(&str, &str)] = &[
+    ("budgie-desktop", "budgie"),
+    ("cinnamon2d", "cinnamon"),
+    ("x-cinnamon", "cinnamon"),
+    ("gnome-classic", "gnome"),
+    ("gnome-flashback", "gnome"),
+    ("gnome-xorg", "gnome"),
+    ("gnome-wayland", "gnome"),
+    ("i3-with-shmlog", "i3"),
+    ("plasma", "kde"),
+    ("plasmawayland", "kde"),
+    ("plasmax11", "kde"),
+    ("plasma-wayland", "kde"),
+    ("lxsession", "lxde"),
+    ("xfce4", "xfce"),
+    ("xfce4-session", "xfce"),
+];
+
+/// Directories containing the session files of installed desktops
+const SESSION_DIRS : &[&str] = &["usr/share/wayland-sessions", "usr/share/xsessions"];
+
+/// Display manager configuration files and the keys selecting the default session
+const DISPLAY_MANAGER_CONFIGS : &[(&str, &[&str])] = &[
+    ("etc/sddm.conf", &["Session"]),
+    ("etc/lightdm/lightdm.conf", &["user-session", "autologin-session"]),
+];
+
+/// Normalises a desktop name, e.g. `KDE`, `plasmawayland.desktop` or `X-Cinnamon`, to a common identifier like `kde` or `cinnamon`.
+/// Names without a known alias are only lowercased.
+pub fn normalize_desktop(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    let name = name.strip_suffix(".desktop").unwrap_or(&name);
+    DESKTOP_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map(|(_, desktop)| desktop.to_string())
+        .unwrap_or_else(|| name.to_owned())
+}
+
+/// Collects the names of desktops that may be in use, most reliable first.
+///
+/// Considers `XDG_CURRENT_DESKTOP` and `DESKTOP_SESSION`, which are unset over SSH, under sudo or on a TTY,
+/// and falls back to the files below `root`: the sessions selected in display manager and AccountsService configs,
+/// then all installed session files.
+pub fn desktop_candidates(root: &Path) -> Vec<String> {
+    let mut candidates = Vec::new();
+    if let Ok(current_desktop) = std::env::var("XDG_CURRENT_DESKTOP") {
+        // may contain multiple desktops, e.g. `GNOME:GNOME-Flashback` or `Budgie:GNOME`
+        candidates.extend(current_desktop.split(':').map(|desktop| desktop.to_owned()));
+    }
+    if let Ok(desktop_session) = std::env::var("DESKTOP_SESSION") {
+        // may be a path to the session file
+        candidates.extend(Path::new(&desktop_session).file_name().map(|name| name.to_string_lossy().into_owned()));
+    }
+    let user = std::env::var("SUDO_USER").or_else(|_| std::env::var("USER")).ok();
+    candidates.extend(file_candidates(root, user.as_deref()));
+    candidates
+}
+
+/// Collects desktop names from the display manager and AccountsService configs as well as installed session files below `root`.
+fn file_candidates(root: &Path, user: Option<&str>) -> Vec<String> {
+    let mut candidates = Vec::new();
+
+    let mut configs : Vec<(std::path::PathBuf, &[&str])> = DISPLAY_MANAGER_CONFIGS
+        .iter()
+        .map(|(path, keys)| (root.join(path), *keys))
+        .collect();
+    if let Ok(entries) = fs::read_dir(root.join("etc/sddm.conf.d")) {
+        configs.extend(entries.filter_map(Result::ok).map(|entry| (entry.path(), &["Session"][..])));
+    }
+    if let Some(user) = user {
+        configs.push((root.join("var/lib/AccountsService/users").join(user), &["Session", "XSession"]));
+    }
+    for (config_path, keys) in configs {
+        if let Ok(config) = fs::read_to_string(config_path) {
+            candidates.extend(config_values(&config, keys));
+        }
+    }
+
+    for session_dir in SESSION_DIRS {
+        let Ok(entries) = fs::read_dir(root.join(session_dir)) else {
+            continue;
+        };
+        let mut sessions : Vec<String> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|file_name| file_name.ends_with(".desktop"))
+            .collect();
+        sessions.sort();
+        candidates.extend(sessions);
+    }
+    candidates
+}
+
+/// Returns the non-empty values of `keys` in an ini-style config.
+fn config_values<'a>(config: &'a str, keys: &'a [&str]) -> impl Iterator<Item = String> + 'a {
+    config.lines()
+        .filter_map(|line| line.split_once('='))
+        .filter(|(key, value)| keys.contains(&key.trim()) && !value.trim().is_empty())
+        .map(|(_, value)| value.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_desktop() {
+        assert_eq!("kde", normalize_desktop("KDE"));
+        assert_eq!("kde", normalize_desktop("plasmawayland.desktop"));
+        assert_eq!("cinnamon", normalize_desktop("X-Cinnamon"));
+        assert_eq!("gnome", normalize_desktop("GNOME-Flashback"));
+        assert_eq!("sway", normalize_desktop("sway"));
+    }
+
+    #[test]
+    fn test_file_candidates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("etc/lightdm")).unwrap();
+        fs::create_dir_all(root.join("usr/share/xsessions")).unwrap();
+        fs::create_dir_all(root.join("var/lib/AccountsService/users")).unwrap();
+
+        fs::write(root.join("etc/lightdm/lightdm.conf"), "[Seat:*]\n#user-session=default\nuser-session=xfce\n").unwrap();
+        fs::write(root.join("var/lib/AccountsService/users/alice"), "[User]\nSession=\nXSession=i3\n").unwrap();
+        fs::write(root.join("usr/share/xsessions/openbox.desktop"), "").unwrap();
+        fs::write(root.join("usr/share/xsessions/cinnamon.desktop"), "").unwrap();
+
+        let expected = vec!["xfce", "i3", "cinnamon.desktop", "openbox.desktop"];
+        assert_eq!(expected, file_candidates(root, Some("alice")));
+    }
+}
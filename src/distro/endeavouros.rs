@@ -0,0 +1,30 @@
+use std::collections::BTreeMap;
+
+use crate::{PackageGroupMap, PackageGroup};
+
+use super::{DistroProvider, SystemConfiguration, TemplateSource, fetch_package_list, parse_plain_list};
+
+const PACKAGE_LIST_BASE_URL : &str = "https://raw.githubusercontent.com/endeavouros-team/EndeavourOS-packages-lists/master";
+const BASE_LIST : &str = "eos-base-group";
+
+/// Templates from the package lists used by the EndeavourOS installer
+pub struct EndeavourOs;
+
+impl DistroProvider for EndeavourOs {
+    fn id(&self) -> &'static str { "endeavouros" }
+    fn name(&self) -> &'static str { "EndeavourOS" }
+    fn os_release_ids(&self) -> &'static [&'static str] { &["endeavouros"] }
+    fn desktops(&self) -> &'static [&'static str] {
+        &["budgie", "cinnamon", "gnome", "i3", "kde", "lxde", "lxqt", "mate", "qtile", "sway", "xfce"]
+    }
+
+    fn generate_groups(&self, system_config: &SystemConfiguration, source: &TemplateSource) -> anyhow::Result<PackageGroupMap> {
+        let mut group_map : PackageGroupMap = BTreeMap::new();
+
+        for list in [Some(BASE_LIST), system_config.desktop.as_deref()].into_iter().flatten() {
+            let members = fetch_package_list(source, PACKAGE_LIST_BASE_URL, list, parse_plain_list)?;
+            group_map.insert(format!("{}-{}", system_config.distro, list), PackageGroup::from_members(members));
+        }
+        Ok(group_map)
+    }
+}
@@ -0,0 +1,41 @@
+use std::collections::BTreeMap;
+
+use crate::{PackageGroupMap, PackageGroup};
+
+use super::{DistroProvider, SystemConfiguration, TemplateSource, desktop, fetch_package_list, parse_manjaro_tools_list};
+
+const ISO_PROFILES_BASE_URL : &str = "https://gitlab.com/garuda-linux/tools/iso-profiles/-/raw/master";
+
+/// Templates from the package lists of the Garuda ISO profiles, which use the manjaro-tools format
+pub struct Garuda;
+
+impl DistroProvider for Garuda {
+    fn id(&self) -> &'static str { "garuda" }
+    fn name(&self) -> &'static str { "Garuda" }
+    fn os_release_ids(&self) -> &'static [&'static str] { &["garuda"] }
+    fn desktops(&self) -> &'static [&'static str] {
+        &["cinnamon", "dr460nized", "gnome", "hyprland", "i3", "mate", "sway", "xfce"]
+    }
+
+    fn desktop_id(&self, desktop: &str) -> Option<&'static str> {
+        // the KDE edition is named after its theme
+        match desktop::normalize_desktop(desktop).as_str() {
+            "kde" => Some("dr460nized"),
+            desktop => self.desktops().iter().find(|id| **id == desktop).copied(),
+        }
+    }
+
+    fn generate_groups(&self, system_config: &SystemConfiguration, source: &TemplateSource) -> anyhow::Result<PackageGroupMap> {
+        let mut lists = vec![("base", String::from("shared/Packages-Root"))];
+        if let Some(desktop) = &system_config.desktop {
+            lists.push((desktop, format!("garuda/{}/Packages-Desktop", desktop)));
+        }
+
+        let mut group_map : PackageGroupMap = BTreeMap::new();
+        for (list, url_path) in lists {
+            let members = fetch_package_list(source, ISO_PROFILES_BASE_URL, &url_path, parse_manjaro_tools_list)?;
+            group_map.insert(format!("{}-{}", system_config.distro, list), PackageGroup::from_members(members));
+        }
+        Ok(group_map)
+    }
+}
@@ -0,0 +1,36 @@
+use std::collections::BTreeMap;
+
+use crate::{PackageGroupMap, PackageGroup};
+
+use super::{DistroProvider, SystemConfiguration, TemplateSource, fetch_package_list, parse_manjaro_tools_list};
+
+const ISO_PROFILES_BASE_URL : &str = "https://gitlab.manjaro.org/profiles-and-settings/iso-profiles/-/raw/master";
+const OFFICIAL_DESKTOPS : &[&str] = &["gnome", "kde", "xfce"];
+
+/// Templates from the package lists of the Manjaro ISO profiles
+pub struct Manjaro;
+
+impl DistroProvider for Manjaro {
+    fn id(&self) -> &'static str { "manjaro" }
+    fn name(&self) -> &'static str { "Manjaro" }
+    fn os_release_ids(&self) -> &'static [&'static str] { &["manjaro", "manjaro-arm"] }
+    fn desktops(&self) -> &'static [&'static str] {
+        &["budgie", "cinnamon", "gnome", "i3", "kde", "mate", "sway", "xfce"]
+    }
+
+    fn generate_groups(&self, system_config: &SystemConfiguration, source: &TemplateSource) -> anyhow::Result<PackageGroupMap> {
+        let mut lists = vec![("base", String::from("shared/Packages-Root"))];
+        if let Some(desktop) = &system_config.desktop {
+            // community editions are maintained in a separate directory
+            let edition_dir = if OFFICIAL_DESKTOPS.contains(&desktop.as_str()) { "manjaro" } else { "community" };
+            lists.push((desktop, format!("{}/{}/Packages-Desktop", edition_dir, desktop)));
+        }
+
+        let mut group_map : PackageGroupMap = BTreeMap::new();
+        for (list, url_path) in lists {
+            let members = fetch_package_list(source, ISO_PROFILES_BASE_URL, &url_path, parse_manjaro_tools_list)?;
+            group_map.insert(format!("{}-{}", system_config.distro, list), PackageGroup::from_members(members));
+        }
+        Ok(group_map)
+    }
+}
@@ -0,0 +1,246 @@
+use std::{path::{Path, PathBuf}, time::Duration, fs};
+
+use anyhow::{Context, bail};
+use reqwest::{StatusCode, header::{ETAG, IF_NONE_MATCH}};
+
+/// Default timeout of HTTP requests for package lists
+pub const DEFAULT_TIMEOUT : Duration = Duration::from_secs(30);
+
+/// Location the package lists of distro providers are read from.
+#[derive(Debug, Clone)]
+pub enum TemplateSource {
+    /// Fetches package lists over HTTP
+    Http(HttpSource),
+    /// Reads package lists from a local directory with the same layout as the provider's base URL
+    Directory(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpSource {
+    /// Replaces the base URL of the provider, e.g. to use a mirror
+    pub base_url: Option<String>,
+    /// Directory to cache responses in. Cached responses are revalidated with their ETag
+    /// and used if the server can not be reached
+    pub cache_dir: Option<PathBuf>,
+    pub timeout: Duration,
+}
+
+impl Default for TemplateSource {
+    /// Fetches from the provider's upstream URLs without caching
+    fn default() -> Self {
+        Self::Http(HttpSource { base_url: None, cache_dir: None, timeout: DEFAULT_TIMEOUT })
+    }
+}
+
+impl TemplateSource {
+    /// Parses a source passed by the user: `http://` and `https://` URLs replace the provider's base URL,
+    /// `file://` URLs and all other values are treated as local directories.
+    pub fn parse(source: &str, cache_dir: Option<PathBuf>, timeout: Duration) -> Self {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            Self::Http(HttpSource { base_url: Some(source.to_owned()), cache_dir, timeout })
+        } else {
+            Self::Directory(PathBuf::from(source.strip_prefix("file://").unwrap_or(source)))
+        }
+    }
+
+    /// Reads the package list at `path` relative to the provider's `base_url`.
+    pub fn fetch(&self, base_url: &str, path: &str) -> anyhow::Result<String> {
+        match self {
+            Self::Http(http_source) => {
+                let base_url = http_source.base_url.as_deref().unwrap_or(base_url);
+                http_source.fetch(&join_url(base_url, path))
+            },
+            Self::Directory(dir) => {
+                let file_path = dir.join(path);
+                log::debug!("Reading package list '{}'", file_path.to_string_lossy());
+                fs::read_to_string(&file_path).with_context(|| format!("Failed to read package list '{}'", file_path.to_string_lossy()))
+            },
+        }
+    }
+}
+
+impl HttpSource {
+    fn fetch(&self, url: &str) -> anyhow::Result<String> {
+        let cache_paths = self.cache_dir.as_ref().map(|cache_dir| CachePaths::new(cache_dir, url));
+        let cached_etag = cache_paths.as_ref().and_then(|paths| fs::read_to_string(&paths.etag).ok());
+
+        match self.request(url, cached_etag.as_deref()) {
+            Ok(Response::NotModified) => {
+                log::debug!("'{}' is unmodified, using cached package list", url);
+                let cache_paths = cache_paths.context("Server reported an unmodified package list without a cached copy")?;
+                fs::read_to_string(&cache_paths.body).context("Failed to read cached package list")
+            },
+            Ok(Response::Body { body, etag }) => {
+                // a failed cache write only costs the next revalidation, the download itself succeeded
+                if let Some(Err(err)) = cache_paths.map(|cache_paths| cache_paths.store(&body, etag.as_deref())) {
+                    log::warn!("Failed to cache package list of '{}': {}", url, err);
+                }
+                Ok(body)
+            },
+            Err(err) => {
+                // fall back to the cached copy, so templating also works offline
+                match cache_paths.and_then(|paths| fs::read_to_string(paths.body).ok()) {
+                    Some(body) => {
+                        log::warn!("Failed to fetch '{}', using cached package list: {:#}", url, err);
+                        Ok(body)
+                    },
+                    None => Err(err),
+                }
+            }
+        }
+    }
+
+    fn request(&self, url: &str, etag: Option<&str>) -> anyhow::Result<Response> {
+        // TODO(medium, optimization): explore possibilities to run as async instead
+        let client = reqwest::blocking::Client::builder()
+            .timeout(self.timeout)
+            .build()?;
+        let mut request = client.get(url);
+        if let Some(etag) = etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        log::debug!("Fetching '{}'", url);
+        let response = request.send().with_context(|| format!("Failed to fetch '{}'", url))?;
+        log::trace!("Response status of '{}': {}", url, response.status());
+
+        match response.status() {
+            StatusCode::NOT_MODIFIED => Ok(Response::NotModified),
+            status if status.is_success() => {
+                let etag = response.headers().get(ETAG).and_then(|etag| etag.to_str().ok()).map(|etag| etag.to_owned());
+                Ok(Response::Body { body: response.text()?, etag })
+            },
+            status => bail!("Failed to fetch '{}': {}", url, status),
+        }
+    }
+}
+
+enum Response {
+    NotModified,
+    Body { body: String, etag: Option<String> },
+}
+
+/// Paths of the files caching the response for a URL
+struct CachePaths {
+    body: PathBuf,
+    etag: PathBuf,
+}
+
+impl CachePaths {
+    /// The files are named by a hash of the full URL, so distinct URLs never share a cache entry.
+    fn new(cache_dir: &Path, url: &str) -> Self {
+        let file_name = format!("{:016x}", fnv1a_64(url.as_bytes()));
+        Self {
+            body: cache_dir.join(&file_name),
+            etag: cache_dir.join(file_name + ".etag"),
+        }
+    }
+
+    fn store(&self, body: &str, etag: Option<&str>) -> std::io::Result<()> {
+        if let Some(parent) = self.body.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.body, body)?;
+        match etag {
+            Some(etag) => fs::write(&self.etag, etag),
+            None => match fs::remove_file(&self.etag) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+/// 64-bit FNV-1a hash. Used instead of the std hashers, as its output is guaranteed to be stable across releases.
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS : u64 = 0xcbf29ce484222325;
+    const PRIME : u64 = 0x100000001b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| (hash ^ *byte as u64).wrapping_mul(PRIME))
+}
+
+fn join_url(base_url: &str, path: &str) -> String {
+    format!("{}/{}", base_url.trim_end_matches('/'), path.trim_start_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::BTreeSet, io::{BufRead, BufReader, Write}, net::TcpListener, thread};
+
+    /// Serves `requests` HTTP requests with an ETag, answering conditional requests with `304 Not Modified`.
+    /// Returns the base URL of the server and a handle returning whether each request was answered with 304.
+    fn serve(body: &'static str, etag: &'static str, requests: usize) -> (String, thread::JoinHandle<Vec<bool>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let mut not_modified = Vec::new();
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut request_headers = Vec::new();
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    request_headers.push(line.to_lowercase());
+                }
+                let revalidated = request_headers.contains(&format!("if-none-match: {}", etag));
+                if revalidated {
+                    write!(stream, "HTTP/1.1 304 Not Modified\r\nETag: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", etag).unwrap();
+                } else {
+                    write!(stream, "HTTP/1.1 200 OK\r\nETag: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", etag, body.len(), body).unwrap();
+                }
+                not_modified.push(revalidated);
+            }
+            not_modified
+        });
+        (base_url, handle)
+    }
+
+    #[test]
+    fn test_http_source_revalidates_cache() {
+        let cache_dir = tempfile::tempdir().unwrap();
+
+        let (base_url, server) = serve("base\nlinux\n", "\"v1\"", 2);
+        let source = TemplateSource::Http(HttpSource { base_url: None, cache_dir: Some(cache_dir.path().to_path_buf()), timeout: DEFAULT_TIMEOUT });
+
+        assert_eq!("base\nlinux\n", source.fetch(&base_url, "lists/base").unwrap());
+        assert_eq!("base\nlinux\n", source.fetch(&base_url, "lists/base").unwrap());
+        assert_eq!(vec![false, true], server.join().unwrap());
+
+        // the server is gone now, so the cached copy is used
+        assert_eq!("base\nlinux\n", source.fetch(&base_url, "lists/base").unwrap());
+    }
+
+    #[test]
+    fn test_http_source_cache_write_failure() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        // the cache directory can not be created, as a file is in the way
+        let cache_file = cache_dir.path().join("cache");
+        fs::write(&cache_file, "").unwrap();
+
+        let (base_url, server) = serve("base\n", "\"v1\"", 1);
+        let source = TemplateSource::Http(HttpSource { base_url: None, cache_dir: Some(cache_file), timeout: DEFAULT_TIMEOUT });
+        assert_eq!("base\n", source.fetch(&base_url, "lists/base").unwrap());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_cache_paths_distinct() {
+        let cache_dir = Path::new("/cache");
+        let urls = ["https://example.com/lists/base", "https://example.com/lists_base", "https://example.com/lists/base?", "https://example.com/Lists/base"];
+        let paths : BTreeSet<PathBuf> = urls.iter().map(|url| CachePaths::new(cache_dir, url).body).collect();
+        assert_eq!(urls.len(), paths.len());
+        assert_eq!(CachePaths::new(cache_dir, urls[0]).body, CachePaths::new(cache_dir, urls[0]).body);
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert!(matches!(TemplateSource::parse("https://mirror.example.com/lists", None, DEFAULT_TIMEOUT),
+            TemplateSource::Http(HttpSource { base_url: Some(_), .. })));
+        assert!(matches!(TemplateSource::parse("file:///srv/lists", None, DEFAULT_TIMEOUT),
+            TemplateSource::Directory(dir) if dir == Path::new("/srv/lists")));
+        assert!(matches!(TemplateSource::parse("./lists", None, DEFAULT_TIMEOUT),
+            TemplateSource::Directory(dir) if dir == Path::new("./lists")));
+    }
+}
@@ -14,7 +14,7 @@ use super::{PackageId, GroupId, config::TargetId};
 #[derive(Error, Debug)]
 pub enum Error {
     // not found errors
-    #[error("Active target `{0}` not found")]
+    #[error("Target `{0}` not found")]
     TargetNotFound(TargetId),
 
     #[error("Package file `{package_file}` not found")]
@@ -30,9 +30,23 @@ pub enum Error {
     PackageNotFound {
         package: PackageId
     },
+    #[error("Variable `{variable}` used in group `{group}` is not defined")]
+    UndefinedVariable {
+        variable: String,
+        group: GroupId,
+    },
+    #[error("Member `{member}` of group `{group}` interpolates to `{interpolated}`, which is not a valid package name")]
+    InvalidInterpolation {
+        member: PackageId,
+        interpolated: PackageId,
+        group: GroupId,
+    },
 
 
     // already exists errors
+    #[error("Target `{0}` already exists")]
+    TargetAlreadyExists(TargetId),
+
     #[error("Package file `{package_file}` already exists")]
     PackageFileAlreadyExists {
         package_file: PathBuf,
@@ -67,6 +81,11 @@ pub enum Error {
     },
 
     // other errors
+    #[error("Failed to roll back changes after error: {error}")]
+    RollbackFailed {
+        error: Box<Error>,
+        source: Box<Error>,
+    },
     #[error("Config file has no parent directory")]
     ConfigFileHasNoParentDir {
         path: PathBuf
@@ -0,0 +1,146 @@
+use std::{collections::{BTreeMap, BTreeSet}, path::PathBuf};
+
+use super::{GroupId, PackageGroup, PackageId, config::{Config, PackageConfiguration, TargetId}, variables::{self, Variables}};
+
+/// Finds the groups declaring `package` once their members are interpolated, with the file containing them
+/// and the targets they declare it for.
+///
+/// Root groups are interpolated with the variables of each of their targets and only returned with the targets they declare `package` for.
+/// Groups that are not a root group of any target are interpolated with the global variables and returned without targets.
+pub fn declaring_groups<'a>(config: &'a Config, package_config: &'a PackageConfiguration, package: &PackageId) -> Vec<(&'a PathBuf, &'a GroupId, Vec<&'a TargetId>)> {
+    let declares = |group: &PackageGroup, variables: &Variables| group.members
+        .iter()
+        .any(|member| variables::interpolate(member, variables).is_ok_and(|member| &member == package));
+
+    package_config.iter_groups()
+        .filter_map(|(group_id, group)| {
+            let mut targets = config.targets_with_root_group(group_id).peekable();
+            let declared_for = if targets.peek().is_none() {
+                declares(group, &config.variables).then(Vec::new)
+            } else {
+                let targets : Vec<&TargetId> = targets.filter(|target| declares(group, &config.target_variables(&config.targets[*target]))).collect();
+                (!targets.is_empty()).then_some(targets)
+            }?;
+            let (file_path, _) = package_config.find_group(group_id)?;
+            Some((file_path, group_id, declared_for))
+        })
+        .collect()
+}
+
+/// Finds the dependency chains through which `package` is required by any of the `configured` packages.
+///
+/// Each chain starts with `package` and ends with a configured package, listing the dependants in between.
+/// Only the shortest chain to each configured package is returned.
+/// `required_by` is queried once per dependency level with all packages of that level
+/// and is expected to behave like `pacman::packages_required_by`.
+pub fn dependency_chains<F>(package: &PackageId, configured: &BTreeSet<&PackageId>, mut required_by: F) -> anyhow::Result<Vec<Vec<PackageId>>>
+where
+    F: FnMut(Vec<PackageId>) -> anyhow::Result<Vec<(PackageId, Vec<PackageId>)>>
+{
+    // maps each visited package to the package it was reached from
+    let mut reached_from : BTreeMap<PackageId, Option<PackageId>> = BTreeMap::new();
+    reached_from.insert(package.clone(), None);
+
+    let mut chain_ends = Vec::new();
+    let mut level = vec![package.clone()];
+
+    while !level.is_empty() {
+        let mut next_level = Vec::new();
+        for (dependency, dependants) in required_by(level)? {
+            for dependant in dependants {
+                if reached_from.contains_key(&dependant) {
+                    continue;
+                }
+                reached_from.insert(dependant.clone(), Some(dependency.clone()));
+                // the search ends at configured packages, as they explain the installation
+                if configured.contains(&dependant) {
+                    chain_ends.push(dependant);
+                } else {
+                    next_level.push(dependant);
+                }
+            }
+        }
+        level = next_level;
+    }
+
+    let chains = chain_ends
+        .into_iter()
+        .map(|chain_end| {
+            let mut chain = vec![chain_end];
+            while let Some(Some(previous)) = chain.last().and_then(|last| reached_from.get(last)) {
+                chain.push(previous.clone());
+            }
+            chain.reverse();
+            chain
+        })
+        .collect();
+    Ok(chains)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::TargetConfig, test_util::{config, package_config}};
+
+    fn required_by(packages: Vec<PackageId>) -> anyhow::Result<Vec<(PackageId, Vec<PackageId>)>> {
+        let dependants : BTreeMap<&str, Vec<&str>> = [
+            ("zlib", vec!["curl", "openssl"]),
+            ("curl", vec!["git", "pacman"]),
+            ("openssl", vec!["curl", "python"]),
+            ("python", vec!["meson"]),
+        ].into();
+        Ok(packages
+            .into_iter()
+            .map(|package| {
+                let package_dependants = dependants.get(package.as_str())
+                    .map(|package_dependants| package_dependants.iter().map(|dependant| dependant.to_string()).collect())
+                    .unwrap_or_default();
+                (package, package_dependants)
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_declaring_groups() {
+        let package_config = package_config(&[("kernel", &["${kernel}-headers"]), ("extra", &["${kernel}-headers", "${gpu}"]), ("base", &["bash"])]);
+        let lts = TargetConfig { root_groups: ["kernel".to_owned()].into(), variables: [("kernel".to_owned(), "linux-lts".to_owned())].into() };
+        let zen = TargetConfig { root_groups: ["kernel".to_owned(), "base".to_owned()].into(), variables: [("kernel".to_owned(), "linux-zen".to_owned())].into() };
+        let mut config = config([("lts", lts), ("zen", zen)]);
+        config.variables.insert("kernel".to_owned(), "linux-lts".to_owned());
+
+        let declaring = |package: &str| -> Vec<(String, Vec<String>)> {
+            declaring_groups(&config, &package_config, &package.to_owned())
+                .into_iter()
+                .map(|(_, group, targets)| (group.clone(), targets.into_iter().cloned().collect()))
+                .collect()
+        };
+        // groups without target are interpolated with the global variables
+        assert_eq!(vec![("extra".to_owned(), vec![]), ("kernel".to_owned(), vec!["lts".to_owned()])], declaring("linux-lts-headers"));
+        assert_eq!(vec![("kernel".to_owned(), vec!["zen".to_owned()])], declaring("linux-zen-headers"));
+        assert!(declaring("${kernel}-headers").is_empty());
+        assert!(declaring("${gpu}").is_empty());
+    }
+
+    #[test]
+    fn test_dependency_chains() {
+        let git = "git".to_owned();
+        let meson = "meson".to_owned();
+        let configured : BTreeSet<&PackageId> = [&git, &meson].into();
+
+        let chains = dependency_chains(&"zlib".to_owned(), &configured, required_by).unwrap();
+        let expected = vec![
+            vec!["zlib", "curl", "git"],
+            vec!["zlib", "openssl", "python", "meson"],
+        ];
+        assert_eq!(expected, chains);
+    }
+
+    #[test]
+    fn test_dependency_chains_not_required() {
+        let git = "git".to_owned();
+        let configured : BTreeSet<&PackageId> = [&git].into();
+
+        let chains = dependency_chains(&"python".to_owned(), &configured, required_by).unwrap();
+        assert!(chains.is_empty());
+    }
+}
@@ -0,0 +1,305 @@
+use std::{collections::BTreeSet, fmt::Display, path::Path};
+
+use serde::Serialize;
+
+use super::{GroupId, PackageId, config::{PackageConfiguration, TargetConfig, TargetId}};
+
+/// Prefix of the names of all generated packages, so they are easy to identify in the pacman database
+const PKGNAME_PREFIX : &str = "impaccable";
+
+/// A PKGBUILD of a metapackage, only consisting of dependencies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pkgbuild {
+    pub pkgname: String,
+    pub pkgver: String,
+    pub pkgdesc: String,
+    pub depends: BTreeSet<PackageId>,
+}
+
+impl Pkgbuild {
+    /// Creates a metapackage depending on `depends`.
+    /// `pkgver` has to increase whenever the dependencies change, so pacman treats the rebuilt package as an upgrade.
+    pub fn new(pkgname: String, pkgver: String, pkgdesc: String, depends: BTreeSet<PackageId>) -> Self {
+        Self { pkgname, pkgver, pkgdesc, depends }
+    }
+
+    /// Creates a metapackage depending on all packages of the target.
+    pub fn from_target(target_id: &TargetId, target: &TargetConfig, package_config: &PackageConfiguration, pkgver: &str) -> Self {
+        Self::new(
+            target_pkgname(target_id),
+            pkgver.to_owned(),
+            format!("Packages of the impaccable target '{}'", target_id),
+            package_config.packages_of_groups(&target.root_groups).cloned().collect(),
+        )
+    }
+
+    /// Creates a metapackage per root group of the target, as well as a metapackage for the target depending on them.
+    /// The target's metapackage is the last element.
+    pub fn per_group(target_id: &TargetId, target: &TargetConfig, package_config: &PackageConfiguration, pkgver: &str) -> Vec<Self> {
+        let mut pkgbuilds : Vec<Self> = package_config.filter_groups(&target.root_groups)
+            .map(|(group_id, group)| Self::new(
+                group_pkgname(group_id),
+                pkgver.to_owned(),
+                format!("Packages of the impaccable group '{}'", group_id),
+                group.members.clone(),
+            ))
+            .collect();
+        let group_pkgnames = pkgbuilds.iter().map(|pkgbuild| pkgbuild.pkgname.clone()).collect();
+        pkgbuilds.push(Self::new(
+            target_pkgname(target_id),
+            pkgver.to_owned(),
+            format!("Groups of the impaccable target '{}'", target_id),
+            group_pkgnames,
+        ));
+        pkgbuilds
+    }
+}
+
+impl Display for Pkgbuild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# Generated by impaccable, do not edit")?;
+        writeln!(f, "pkgname={}", self.pkgname)?;
+        writeln!(f, "pkgver={}", self.pkgver)?;
+        writeln!(f, "pkgrel=1")?;
+        writeln!(f, "pkgdesc={}", shell_quote(&self.pkgdesc))?;
+        writeln!(f, "arch=('any')")?;
+        writeln!(f, "depends=(")?;
+        for package in &self.depends {
+            writeln!(f, "  {}", shell_quote(package))?;
+        }
+        writeln!(f, ")")
+    }
+}
+
+/// Quotes a value for bash, which sources PKGBUILDs, so it is never expanded.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Base image of generated Containerfiles
+const CONTAINERFILE_BASE_IMAGE : &str = "archlinux:base";
+
+/// A Containerfile installing the packages of a target, with a layer per group so unchanged groups stay cached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Containerfile {
+    pub target_id: TargetId,
+    /// Packages installed per layer. Packages contained in multiple groups are only installed in the first layer
+    pub layers: Vec<(GroupId, BTreeSet<PackageId>)>,
+    /// Packages excluded because they are not available in the repositories, e.g. AUR packages
+    pub excluded: BTreeSet<PackageId>,
+}
+
+impl Containerfile {
+    /// Creates a Containerfile for the target, only installing packages contained in `repository_packages`,
+    /// e.g. the names installable from the official repositories returned by [`crate::pacman::query_repository_packages`].
+    pub fn new(target_id: &TargetId, target: &TargetConfig, package_config: &PackageConfiguration, repository_packages: &BTreeSet<PackageId>) -> Self {
+        let mut installed : BTreeSet<&PackageId> = BTreeSet::new();
+        let mut excluded = BTreeSet::new();
+        let mut layers = Vec::new();
+
+        for (group_id, group) in package_config.filter_groups(&target.root_groups) {
+            let mut layer_packages = BTreeSet::new();
+            for package in &group.members {
+                if !repository_packages.contains(package) {
+                    excluded.insert(package.clone());
+                } else if installed.insert(package) {
+                    layer_packages.insert(package.clone());
+                }
+            }
+            if !layer_packages.is_empty() {
+                layers.push((group_id.clone(), layer_packages));
+            }
+        }
+        Self { target_id: target_id.clone(), layers, excluded }
+    }
+}
+
+impl Display for Containerfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# Generated by impaccable from target '{}', do not edit", self.target_id)?;
+        if !self.excluded.is_empty() {
+            writeln!(f, "# Not available in the repositories, requires an AUR helper:")?;
+            for package in &self.excluded {
+                writeln!(f, "#   {}", package)?;
+            }
+        }
+        writeln!(f, "FROM {}", CONTAINERFILE_BASE_IMAGE)?;
+        writeln!(f, "RUN pacman -Syu --noconfirm && rm -rf /var/cache/pacman/pkg/*")?;
+        for (group_id, packages) in &self.layers {
+            writeln!(f)?;
+            writeln!(f, "# Group '{}'", group_id)?;
+            write!(f, "RUN pacman -S --noconfirm --needed")?;
+            for package in packages {
+                write!(f, " \\\n    {}", package)?;
+            }
+            writeln!(f, " \\\n    && rm -rf /var/cache/pacman/pkg/*")?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders the packages as a plain list, one package per line.
+pub fn render_plain(packages: &BTreeSet<&PackageId>) -> String {
+    packages.iter().map(|package| format!("{}\n", package)).collect()
+}
+
+/// Renders the packages as an archiso `packages.x86_64` file.
+pub fn render_archiso(target_id: &TargetId, packages: &BTreeSet<&PackageId>) -> String {
+    format!("# Packages of the impaccable target '{}'\n{}", target_id, render_plain(packages))
+}
+
+/// Renders the packages as a pacstrap command line installing them into `root`.
+pub fn render_pacstrap(root: &Path, packages: &BTreeSet<&PackageId>) -> String {
+    let mut command_line = format!("pacstrap -K {}", root.to_string_lossy());
+    for package in packages {
+        command_line.push_str(" \\\n  ");
+        command_line.push_str(package);
+    }
+    command_line.push('\n');
+    command_line
+}
+
+/// The subset of the archinstall configuration file set by impaccable
+#[derive(Serialize)]
+struct ArchinstallConfig<'a> {
+    packages: &'a BTreeSet<&'a PackageId>,
+}
+
+/// Renders the packages as an archinstall configuration, to be passed with `archinstall --config`.
+pub fn render_archinstall_json(packages: &BTreeSet<&PackageId>) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&ArchinstallConfig { packages }).map(|json| json + "\n")
+}
+
+/// Name of the metapackage of a target
+pub fn target_pkgname(target_id: &TargetId) -> String {
+    format!("{}-{}", PKGNAME_PREFIX, sanitize_pkgname(target_id))
+}
+
+/// Name of the metapackage of a group
+pub fn group_pkgname(group_id: &GroupId) -> String {
+    format!("{}-group-{}", PKGNAME_PREFIX, sanitize_pkgname(group_id))
+}
+
+/// Checks whether `pkgver` is a valid pacman package version: alphanumeric characters, periods, underscores and plus signs, without the hyphen separating pkgrel.
+pub fn is_valid_pkgver(pkgver: &str) -> bool {
+    !pkgver.is_empty() && pkgver.chars().all(|c| c.is_ascii_alphanumeric() || "._+".contains(c))
+}
+
+/// Converts a name to only contain characters allowed in pacman package names.
+fn sanitize_pkgname(name: &str) -> String {
+    name.chars()
+        .map(|c| c.to_ascii_lowercase())
+        .map(|c| if c.is_ascii_alphanumeric() || "@._+-".contains(c) { c } else { '-' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_pkgname() {
+        assert_eq!("impaccable-amd_desktop", target_pkgname(&"amd_desktop".to_owned()));
+        assert_eq!("impaccable-group-my-group-", group_pkgname(&"My Group!".to_owned()));
+    }
+
+    #[test]
+    fn test_pkgbuild_version() {
+        use crate::test_util::{package_config, set};
+
+        let package_config = package_config(&[("base", &["bat"]), ("dev", &["rustup"])]);
+        let target = TargetConfig::from_root_groups(set(&["base", "dev"]));
+        let pkgbuilds = Pkgbuild::per_group(&"test".to_owned(), &target, &package_config, "42");
+        assert!(pkgbuilds.iter().all(|pkgbuild| pkgbuild.pkgver == "42"));
+        assert_eq!(set(&["impaccable-group-base", "impaccable-group-dev"]), pkgbuilds[2].depends);
+
+        assert!(is_valid_pkgver("42"));
+        assert!(is_valid_pkgver("1.2_rc+3"));
+        assert!(!is_valid_pkgver(""));
+        assert!(!is_valid_pkgver("1-2"));
+        assert!(!is_valid_pkgver("1:2"));
+        assert!(!is_valid_pkgver("1 2"));
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let bat = "bat".to_owned();
+        let htop = "htop".to_owned();
+        let packages : BTreeSet<&PackageId> = [&bat, &htop].into();
+
+        assert_eq!("bat\nhtop\n", render_plain(&packages));
+        assert_eq!("# Packages of the impaccable target 'test'\nbat\nhtop\n", render_archiso(&"test".to_owned(), &packages));
+        assert_eq!("pacstrap -K /mnt \\\n  bat \\\n  htop\n", render_pacstrap(Path::new("/mnt"), &packages));
+        assert_eq!("{\n  \"packages\": [\n    \"bat\",\n    \"htop\"\n  ]\n}\n", render_archinstall_json(&packages).unwrap());
+    }
+
+    #[test]
+    fn test_containerfile() {
+        use crate::test_util::{package_config, set};
+
+        let package_config = package_config(&[("base", &["bat", "paru"]), ("dev", &["bat", "rustup"])]);
+        let target = TargetConfig::from_root_groups(set(&["base", "dev"]));
+        let repository_packages = set(&["bat", "rustup"]);
+        let containerfile = Containerfile::new(&"test".to_owned(), &target, &package_config, &repository_packages);
+
+        let expected = r#"# Generated by impaccable from target 'test', do not edit
+# Not available in the repositories, requires an AUR helper:
+#   paru
+FROM archlinux:base
+RUN pacman -Syu --noconfirm && rm -rf /var/cache/pacman/pkg/*
+
+# Group 'base'
+RUN pacman -S --noconfirm --needed \
+    bat \
+    && rm -rf /var/cache/pacman/pkg/*
+
+# Group 'dev'
+RUN pacman -S --noconfirm --needed \
+    rustup \
+    && rm -rf /var/cache/pacman/pkg/*
+"#;
+        assert_eq!(expected, containerfile.to_string());
+    }
+
+    #[test]
+    fn test_pkgbuild_display() {
+        let pkgbuild = Pkgbuild {
+            pkgname: "impaccable-test".to_owned(),
+            pkgver: "42".to_owned(),
+            pkgdesc: "Packages of the impaccable target 'test'".to_owned(),
+            depends: ["bat".to_owned(), "htop".to_owned()].into(),
+        };
+        let expected = r#"# Generated by impaccable, do not edit
+pkgname=impaccable-test
+pkgver=42
+pkgrel=1
+pkgdesc='Packages of the impaccable target '\''test'\'''
+arch=('any')
+depends=(
+  'bat'
+  'htop'
+)
+"#;
+        assert_eq!(expected, pkgbuild.to_string());
+    }
+
+    #[test]
+    fn test_pkgbuild_display_quoting() {
+        use crate::test_util::{package_config, set};
+
+        let package_config = package_config(&[("base", &["$(touch pwned)"])]);
+        let target = TargetConfig::from_root_groups(set(&["base"]));
+        let pkgbuild = Pkgbuild::from_target(&"x'; touch pwned; '".to_owned(), &target, &package_config, "1");
+
+        let rendered = pkgbuild.to_string();
+        assert!(rendered.contains(r#"pkgdesc='Packages of the impaccable target '\''x'\''; touch pwned; '\'''\'''"#));
+        assert!(rendered.contains("  '$(touch pwned)'\n"));
+        // bash parses the quoted values back to the original strings
+        let output = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(format!("{}\nprintf '%s\\n' \"$pkgdesc\" \"${{depends[@]}}\"", rendered))
+            .output()
+            .unwrap();
+        assert_eq!(format!("{}\n$(touch pwned)\n", pkgbuild.pkgdesc), String::from_utf8(output.stdout).unwrap());
+    }
+}
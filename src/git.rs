@@ -0,0 +1,133 @@
+use std::{path::Path, process::{Command, ExitStatus, Stdio}, ffi::OsStr};
+
+use anyhow::{Context, bail};
+
+/// Changes of a repository not reflected in its upstream or vice versa
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepositoryStatus {
+    /// Uncommitted changes in `git status --porcelain` format
+    pub uncommitted: Vec<String>,
+    /// Commits of the upstream branch not merged into `HEAD`, as of the last fetch. `None` if the branch has no upstream
+    pub behind: Option<usize>,
+}
+
+/// Creates a git command running in `dir`, logging the command line.
+fn git<I, S>(dir: &Path, args: I) -> Command
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).args(args);
+    log::debug!("Running {:?}", command);
+    command
+}
+
+/// Checks whether `dir` is inside the work tree of a git repository.
+pub fn is_repository(dir: &Path) -> bool {
+    git(dir, ["rev-parse", "--is-inside-work-tree"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Runs git in `dir` with the passed arguments, inheriting stdin, stdout and stderr.
+pub fn run<I, S>(dir: &Path, args: I) -> anyhow::Result<ExitStatus>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    git(dir, args).status().context("Failed to run git")
+}
+
+/// Commits all changes to `paths`, including new and deleted files. Changes to other paths stay untouched.
+/// Returns `false` if there was nothing to commit.
+pub fn commit(dir: &Path, paths: &[&Path], message: &str) -> anyhow::Result<bool> {
+    let output = git(dir, ["add", "--all", "--"]).args(paths).output().context("Failed to run git add")?;
+    if !output.status.success() {
+        bail!("git add failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    let staged = git(dir, ["diff", "--cached", "--quiet", "--"]).args(paths).status().context("Failed to run git diff")?;
+    if staged.success() {
+        return Ok(false);
+    }
+
+    let output = git(dir, ["commit", "--quiet", "--message", message, "--"]).args(paths).output().context("Failed to run git commit")?;
+    if !output.status.success() {
+        bail!("git commit failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(true)
+}
+
+/// Counts the commits of `HEAD` changing any of `paths`, which only grows as long as the history is not rewritten.
+pub fn commit_count(dir: &Path, paths: &[&Path]) -> anyhow::Result<usize> {
+    let output = git(dir, ["rev-list", "--count", "HEAD", "--"]).args(paths).output().context("Failed to run git rev-list")?;
+    if !output.status.success() {
+        bail!("git rev-list failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().context("Failed to parse commit count of git rev-list")
+}
+
+/// Gets the uncommitted changes below `dir` and how far the current branch is behind its upstream.
+/// Does not fetch, so unpulled changes are only detected as of the last fetch.
+pub fn status(dir: &Path) -> anyhow::Result<RepositoryStatus> {
+    let output = git(dir, ["status", "--porcelain", "--", "."]).output().context("Failed to run git status")?;
+    if !output.status.success() {
+        bail!("git status failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let uncommitted = String::from_utf8(output.stdout)
+        .context("Failed to parse git stdout as utf8")?
+        .lines()
+        .map(|line| line.to_owned())
+        .collect();
+
+    // fails if the branch has no upstream
+    let output = git(dir, ["rev-list", "--count", "HEAD..@{upstream}"]).stderr(Stdio::null()).output().context("Failed to run git rev-list")?;
+    let behind = if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().parse().context("Failed to parse commit count of git rev-list")?)
+    } else {
+        None
+    };
+
+    Ok(RepositoryStatus { uncommitted, behind })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_and_status() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        std::fs::create_dir(dir.join("packages")).unwrap();
+        assert!(git(dir, ["init", "--quiet"]).status().unwrap().success());
+        for (key, value) in [("user.name", "impaccable"), ("user.email", "impaccable@localhost")] {
+            assert!(git(dir, ["config", key, value]).status().unwrap().success());
+        }
+
+        std::fs::write(dir.join("config.toml"), "package_dir = \"packages\"\n").unwrap();
+        std::fs::write(dir.join("packages/base.toml"), "[base]\nmembers = []\n").unwrap();
+        std::fs::write(dir.join("active-target.toml"), "target = \"laptop\"\n").unwrap();
+        assert!(is_repository(dir));
+
+        let paths = [Path::new("config.toml"), Path::new("packages")];
+        assert!(commit(dir, &paths, "add config").unwrap());
+        assert!(!commit(dir, &paths, "nothing changed").unwrap());
+
+        // only the passed paths are committed
+        let status = status(dir).unwrap();
+        assert_eq!(vec!["?? active-target.toml".to_owned()], status.uncommitted);
+        assert_eq!(None, status.behind);
+
+        // commits of other paths are not counted
+        assert_eq!(1, commit_count(dir, &paths).unwrap());
+        assert!(commit(dir, &[Path::new("active-target.toml")], "add active target").unwrap());
+        assert_eq!(1, commit_count(dir, &paths).unwrap());
+        std::fs::write(dir.join("packages/base.toml"), "[base]\nmembers = [\"bash\"]\n").unwrap();
+        assert!(commit(dir, &paths, "add bash").unwrap());
+        assert_eq!(2, commit_count(dir, &paths).unwrap());
+    }
+}
@@ -0,0 +1,112 @@
+use std::{collections::BTreeSet, fmt::Display, path::{Path, PathBuf}, process::Command};
+
+use anyhow::{Context, bail};
+
+use super::{GroupId, PackageId, runner::CommandRunner};
+
+/// When a hook of a group runs during sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HookKind {
+    /// After packages of the group were installed
+    PostInstall,
+    /// Before packages of the group are uninstalled
+    PreRemove,
+}
+
+impl Display for HookKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HookKind::PostInstall => write!(f, "post_install"),
+            HookKind::PreRemove => write!(f, "pre_remove"),
+        }
+    }
+}
+
+/// A hook script of a group, to run for the packages of the group changed by sync
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    pub kind: HookKind,
+    pub group: GroupId,
+    /// Path of the script, resolved against the package directory
+    pub script: PathBuf,
+    /// Packages of the group installed or uninstalled by sync
+    pub packages: BTreeSet<PackageId>,
+}
+
+impl Hook {
+    /// Runs the script in its directory, passing the changed packages as arguments.
+    /// The script has to be executable. It gets the group in `IMPACCABLE_GROUP` and the hook kind in `IMPACCABLE_HOOK`.
+    /// When syncing another system at `root`, the script is copied to its `/tmp` and run inside it with arch-chroot.
+    /// The output is captured in the logs rather than printed.
+    pub fn run(&self, runner: &dyn CommandRunner, root: Option<&Path>) -> anyhow::Result<()> {
+        let Some(root) = root else {
+            let mut command = Command::new(&self.script);
+            if let Some(dir) = self.script.parent() {
+                command.current_dir(dir);
+            }
+            return self.run_command(runner, command);
+        };
+
+        let file_name = self.script.file_name().with_context(|| format!("Hook '{}' is not a file", self.script.to_string_lossy()))?;
+        let chroot_path = Path::new("/tmp").join(format!("impaccable-{}-{}", self.kind, file_name.to_string_lossy()));
+        let staged = root.join(chroot_path.strip_prefix("/").expect("path is absolute"));
+        std::fs::create_dir_all(root.join("tmp"))
+            .and_then(|_| std::fs::copy(&self.script, &staged))
+            .with_context(|| format!("Failed to copy hook '{}' to '{}'", self.script.to_string_lossy(), staged.to_string_lossy()))?;
+
+        let mut command = Command::new("arch-chroot");
+        command.arg(root).arg(&chroot_path);
+        let result = self.run_command(runner, command);
+        if let Err(err) = std::fs::remove_file(&staged) {
+            log::warn!("Failed to remove '{}': {}", staged.to_string_lossy(), err);
+        }
+        result
+    }
+
+    fn run_command(&self, runner: &dyn CommandRunner, mut command: Command) -> anyhow::Result<()> {
+        command
+            .args(&self.packages)
+            .env("IMPACCABLE_GROUP", &self.group)
+            .env("IMPACCABLE_HOOK", self.kind.to_string());
+        log::info!("Running {} hook of group '{}'", self.kind, self.group);
+        let output = runner.output(&mut command).with_context(|| format!("Failed to run hook '{}'", self.script.to_string_lossy()))?;
+
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            log::info!("[{} {}] {}", self.group, self.kind, line);
+        }
+        for line in String::from_utf8_lossy(&output.stderr).lines() {
+            log::warn!("[{} {}] {}", self.group, self.kind, line);
+        }
+        if !output.status.success() {
+            bail!("Hook '{}' exited with {}", self.script.to_string_lossy(), output.status);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::SystemRunner;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_run_hook() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        std::fs::create_dir(dir.join("hooks")).unwrap();
+        let script = dir.join("hooks/docker.sh");
+        std::fs::write(&script, "#!/bin/sh\necho \"$IMPACCABLE_GROUP $IMPACCABLE_HOOK $*\" > hook.out\n[ \"$1\" = docker ]\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut hook = Hook { kind: HookKind::PostInstall, group: "containers".to_owned(), script, packages: ["docker".to_owned()].into() };
+        hook.run(&SystemRunner, None).unwrap();
+        assert_eq!("containers post_install docker\n", std::fs::read_to_string(dir.join("hooks/hook.out")).unwrap());
+
+        hook.kind = HookKind::PreRemove;
+        hook.packages = ["podman".to_owned()].into();
+        assert!(hook.run(&SystemRunner, None).is_err());
+        hook.script = dir.join("missing.sh");
+        assert!(hook.run(&SystemRunner, None).is_err());
+    }
+}
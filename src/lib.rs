@@ -0,0 +1,106 @@
+//! A declarative pacman wrapper: packages are declared in groups, and targets (machine types) reference the groups
+//! they want installed.
+//!
+//! The library exposes the building blocks of the `impaccable` CLI:
+//! - loading the configuration with [`config::ConfigManager::parse`]
+//! - resolving targets with [`config::ConfigManager::target`]
+//! - computing the changes to sync a target with [`plan::Plan::new`]
+//! - applying them with [`plan::Plan::apply`]
+//!
+//! ```no_run
+//! use impaccable::{config::ConfigManager, pacman::{self, PacmanOptions}, plan::Plan, runner::SystemRunner, systemd::SystemctlOptions};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let config_manager = ConfigManager::parse("/home/user/.config/impaccable/config.toml".into())?;
+//! let target = config_manager.target(&"dev_machine".to_owned())?;
+//! let installed = pacman::query_explicitly_installed(&SystemRunner, &PacmanOptions::default())?;
+//! let package_config = config_manager.target_package_config(target)?;
+//! let plan = Plan::new(&installed, &package_config, target);
+//! println!("Missing packages: {:?}", plan.missing_packages());
+//! plan.apply(&SystemRunner, &PacmanOptions::default(), &SystemctlOptions::default(), None)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::{BTreeSet, BTreeMap}, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Configuration files
+pub mod config;
+/// Running the commands of the external tools
+pub mod runner;
+/// Interaction with the pacman CLI
+pub mod pacman;
+/// Interaction with the systemctl CLI, for the units declared by package groups
+pub mod systemd;
+/// Variables interpolated in the members of package groups
+pub mod variables;
+/// Hook scripts run by sync for the groups whose packages changed
+pub mod hooks;
+/// Custom distro support for templating the package configurations
+pub mod distro;
+/// Computing the changes required to sync a target
+pub mod plan;
+/// Snapshots of the packages installed on a system
+pub mod snapshot;
+/// Reports of the drift of many machines from their targets
+pub mod report;
+/// Comparing the packages of targets and snapshots
+pub mod compare;
+/// Explaining why packages are installed
+pub mod explain;
+/// Exporting targets to other formats
+pub mod export;
+/// Interaction with the git CLI, for configurations tracked in a repository
+pub mod git;
+/// Errors of the operations on the configuration
+pub mod error;
+/// Fixtures shared by the tests of the modules
+#[cfg(test)]
+mod test_util;
+
+pub use error::Error;
+/// Result of the operations on the configuration
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Name of a pacman package
+pub type PackageId = String;
+/// Name of a package group
+pub type GroupId = String;
+
+/// A named set of packages, declared in a package file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageGroup {
+    pub members: BTreeSet<PackageId>,
+    /// System units to enable, e.g. `docker.service`
+    #[serde(default, skip_serializing_if="BTreeSet::is_empty")]
+    pub services: BTreeSet<systemd::UnitId>,
+    /// User units to enable for all users, e.g. `pipewire.socket`
+    #[serde(default, skip_serializing_if="BTreeSet::is_empty")]
+    pub user_services: BTreeSet<systemd::UnitId>,
+    /// Script run after packages of the group were installed, relative to the package directory
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub post_install: Option<PathBuf>,
+    /// Script run before packages of the group are uninstalled, relative to the package directory
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub pre_remove: Option<PathBuf>,
+}
+
+impl PackageGroup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn from_members(members: BTreeSet<PackageId>) -> Self {
+        Self { members, ..Default::default() }
+    } 
+    /// Iterates over the system and user units of the group.
+    pub fn units(&self) -> impl Iterator<Item = systemd::Unit> + '_ {
+        let system = self.services.iter().cloned().map(systemd::Unit::system);
+        let user = self.user_services.iter().cloned().map(systemd::Unit::user);
+        system.chain(user)
+    }
+}
+
+/// Default data structure to store Package groups 
+pub type PackageGroupMap = BTreeMap<GroupId, PackageGroup>;
@@ -0,0 +1,65 @@
+use std::{fs::{self, OpenOptions}, path::Path};
+
+use anyhow::Context;
+use log::LevelFilter;
+use simplelog::{ColorChoice, CombinedLogger, ConfigBuilder, SharedLogger, TermLogger, TerminalMode, WriteLogger};
+
+/// Maps the `-v`/`-q` counts to the level logged to the terminal. Warnings are shown by default.
+pub fn terminal_level(verbose: u8, quiet: u8) -> LevelFilter {
+    match (verbose, quiet) {
+        (0, 0) => LevelFilter::Warn,
+        (1, _) => LevelFilter::Info,
+        (2, _) => LevelFilter::Debug,
+        (3.., _) => LevelFilter::Trace,
+        (_, 1) => LevelFilter::Error,
+        (_, 2..) => LevelFilter::Off,
+    }
+}
+
+/// Initializes logging to stderr and optionally appends to `log_file`.
+/// The log file always receives at least debug logs, so failed runs can be inspected after the fact.
+pub fn init(terminal_level: LevelFilter, log_file: Option<&Path>) -> anyhow::Result<()> {
+    // the terminal only shows the level and message, to stay readable next to the regular output
+    let terminal_config = ConfigBuilder::new()
+        .set_time_level(LevelFilter::Off)
+        .set_thread_level(LevelFilter::Off)
+        .set_target_level(LevelFilter::Off)
+        .set_location_level(LevelFilter::Off)
+        .build();
+    let mut loggers : Vec<Box<dyn SharedLogger>> = vec![
+        TermLogger::new(terminal_level, terminal_config, TerminalMode::Stderr, ColorChoice::Auto),
+    ];
+
+    if let Some(log_file) = log_file {
+        if let Some(parent_dir) = log_file.parent().filter(|parent_dir| !parent_dir.as_os_str().is_empty()) {
+            fs::create_dir_all(parent_dir).with_context(|| format!("Failed to create directory of log file '{}'", log_file.to_string_lossy()))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_file)
+            .with_context(|| format!("Failed to open log file '{}'", log_file.to_string_lossy()))?;
+        let file_config = ConfigBuilder::new()
+            .set_time_format_rfc3339()
+            .set_thread_level(LevelFilter::Off)
+            .set_target_level(LevelFilter::Error)
+            .build();
+        loggers.push(WriteLogger::new(terminal_level.max(LevelFilter::Debug), file_config, file));
+    }
+
+    CombinedLogger::init(loggers).context("Failed to initialize logging")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_level() {
+        assert_eq!(LevelFilter::Warn, terminal_level(0, 0));
+        assert_eq!(LevelFilter::Debug, terminal_level(2, 0));
+        assert_eq!(LevelFilter::Trace, terminal_level(5, 0));
+        assert_eq!(LevelFilter::Error, terminal_level(0, 1));
+        assert_eq!(LevelFilter::Off, terminal_level(0, 3));
+    }
+}
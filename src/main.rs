@@ -1,28 +1,75 @@
 mod cli;
-mod impaccable;
+mod completions;
+mod logging;
 
 
-use clap::Parser;
-use impaccable::{config::{ConfigManager, ActiveTarget}, pacman, PackageId};
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
+use impaccable::{config::{ConfigManager, ActiveTarget, PackageConfiguration, TargetConfig}, git, pacman, GroupId, PackageId};
 use dialoguer::{Confirm, Editor, theme::ColorfulTheme, Input, FuzzySelect, MultiSelect, Select};
 use directories::ProjectDirs;
-use std::{path::PathBuf, fs::{self, File}, env, io, collections::BTreeSet};
+use std::{path::{Path, PathBuf}, fs::{self, File}, env, io, collections::BTreeSet, process::ExitCode, time::Duration};
 use std::io::Write;
 use anyhow::{Context, bail, anyhow};
-use cli::{Cli, CliCommand, Target, Groups};
+use cli::{Cli, CliCommand, Target, Groups, Export, ExportCommand, ExportFormat, ReportFormat, Template, TemplateCommand, TemplateSourceArgs};
+use regex::Regex;
 
-use crate::impaccable::{pacman::packages_required_by, PackageGroup, GroupId};
+use impaccable::{pacman::{packages_required_by, PacmanOptions}, runner::SystemRunner, systemd::{self, SystemctlOptions}, hooks::HookKind, variables, plan::{ConfirmRemoval, Plan}, snapshot::Snapshot, report::{self, FleetReport}, compare::{self, PackageDiff, PackageGroups}, export::{self, Pkgbuild, Containerfile}, distro::{DistroRegistry, GroupDiff, MergeStrategy, TemplateDiff, source::{TemplateSource, HttpSource}}, PackageGroupMap};
 
-fn main() -> std::result::Result<(), anyhow::Error> {
-    let cli = Cli::parse();
+/// Exit code of the `status` command if the system has drifted from the active target
+const EXIT_STATUS_DRIFTED : u8 = 2;
 
+/// Directories impaccable stores its configuration and cache in by default
+fn project_dirs() -> anyhow::Result<ProjectDirs> {
     // TODO(low, dependency): check if 'directories' crate is even needed, as this only runs on Linux anyway,
     // and its main benefit over just an xdg crate is being cross-platform
-    let default_project_dirs = ProjectDirs::from("dev.nicolasmohr.impaccable", "impaccable devs", "impaccable")
-    .context("Failed to compute ProjectDirs")?;
+    ProjectDirs::from("dev.nicolasmohr.impaccable", "impaccable devs", "impaccable")
+        .context("Failed to compute ProjectDirs")
+}
+
+/// Path of the config file: the CLI override, `IMPACCABLE_CONFIG` or the default in the config directory
+fn config_path(cli_override: Option<PathBuf>, project_dirs: &ProjectDirs) -> PathBuf {
+    if let Some(cli_config_override) = cli_override {
+        cli_config_override
+    } else if let Ok(env_config_override) = env::var("IMPACCABLE_CONFIG") {
+        PathBuf::from(env_config_override)
+    } else {
+        project_dirs.config_dir().join("config.toml")
+    }
+}
+
+fn main() -> std::result::Result<ExitCode, anyhow::Error> {
+    // answers completion requests of the shell and exits, must run before anything is printed
+    CompleteEnv::with_factory(Cli::command).var(completions::COMPLETE_VAR).complete();
+
+    let cli = Cli::parse();
+
+    let log_file = cli.log_file.clone().or_else(|| env::var_os("IMPACCABLE_LOG_FILE").map(PathBuf::from));
+    logging::init(logging::terminal_level(cli.verbose, cli.quiet), log_file.as_deref())?;
+    log::debug!("Running {:?}", env::args().collect::<Vec<_>>());
+
+    // completions do not depend on the configuration, which may not even exist yet
+    if let Some(CliCommand::Completions { shell }) = &cli.command {
+        completions::write_registration(*shell, &mut io::stdout())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let pacman_options = PacmanOptions { root: cli.root.clone(), dbpath: cli.dbpath.clone(), sysroot: cli.sysroot.clone() };
+    let systemctl_options = SystemctlOptions { root: pacman_options.system_root() };
+    let runner = SystemRunner;
+
+    let default_project_dirs = project_dirs()?;
+
+    // git passthrough does not parse the config, so it can be used to repair it
+    if let Some(CliCommand::Git { args }) = &cli.command {
+        let config_path = config_path(cli.config.clone(), &default_project_dirs);
+        let config_dir = impaccable::config::config_dir(&config_path).context("Failed to get directory containing config")?;
+        let status = git::run(config_dir, args)?;
+        return Ok(ExitCode::from(status.code().and_then(|code| u8::try_from(code).ok()).unwrap_or(1)));
+    }
 
     let active_target_path = {
-        if let Some(cli_target_override) = cli.target {
+        if let Some(cli_target_override) = cli.target_file {
             cli_target_override
         } else if let Ok(env_target_override) = env::var("IMPACCABLE_TARGET") {
             PathBuf::from(env_target_override)
@@ -33,25 +80,32 @@ fn main() -> std::result::Result<(), anyhow::Error> {
         }
     };
 
-    let mut config_manager : impaccable::config::ConfigManager = {
-        let config_path = {
-            if let Some(cli_config_override) = cli.config {
-                cli_config_override
-            }
-            else if let Ok(env_config_override) = env::var("IMPACCABLE_CONFIG") {
-                PathBuf::from(env_config_override)
-            } else {
-                let mut default_config_path = default_project_dirs.config_dir().to_path_buf();
-                default_config_path.push("config.toml");
-                default_config_path
-            }
+    // snapshots are taken on machines that may not have a configuration, the active target is only recorded if set
+    if let Some(CliCommand::Snapshot { target }) = &cli.command {
+        let mut snapshot = Snapshot::query(&runner, &pacman_options).context("Failed to take snapshot")?;
+        snapshot.target = match target {
+            Some(target) => Some(target.clone()),
+            None => fs::read_to_string(&active_target_path)
+                .ok()
+                .and_then(|active_target| ActiveTarget::parse(&active_target).ok())
+                .map(|active_target| active_target.target().clone()),
         };
+        print!("{}", toml::to_string_pretty(&snapshot)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // status is meant for scripts, so it fails instead of prompting for a missing config or active target
+    let interactive = !matches!(cli.command, Some(CliCommand::Status));
+
+    let mut config_manager : impaccable::config::ConfigManager = {
+        let config_path = config_path(cli.config, &default_project_dirs);
 
         // Parse the config file. If it is not found, offer to create it instead.
         let config_manager = match ConfigManager::parse(config_path.clone()) {
             Ok(config_manager) => config_manager,
             Err(err) => {
                 match err {
+                    impaccable::Error::ConfigFileNotFound { path: _, source: _ } if !interactive => bail!(err),
                     impaccable::Error::ConfigFileNotFound { path: _, source: _ } => {
                         println!("{}", err);
                         if Confirm::new().with_prompt("Create a new config file?").interact()? {
@@ -85,6 +139,9 @@ fn main() -> std::result::Result<(), anyhow::Error> {
     let mut active_target = match std::fs::read_to_string(&active_target_path) {
         Ok(s) => ActiveTarget::parse(&s).context(format!("Failed to parse active target at '{}'", &active_target_path.to_string_lossy()))?,
         Err(err) => match err.kind() {
+            io::ErrorKind::NotFound if !interactive => {
+                bail!("Failed to find active target file at '{}'. Select a target with `target set`", active_target_path.to_string_lossy())
+            },
             io::ErrorKind::NotFound => {
                 println!("Failed to find active target file at {}", &active_target_path.to_string_lossy());
                 println!("Please select a new active target");
@@ -108,33 +165,74 @@ fn main() -> std::result::Result<(), anyhow::Error> {
         },
     };
 
+    // Set by commands modifying the configuration, used as message if auto-commit is enabled
+    let mut commit_message : Option<String> = None;
+
     // The following code handles the different CLI (sub)commands, then exits.
     match &cli.command {
         None => {},
+        Some(CliCommand::Completions { .. }) | Some(CliCommand::Snapshot { .. }) | Some(CliCommand::Git { .. }) => {
+            // handled before loading the configuration
+        }
         Some(CliCommand::Config) => {
             println!("config: {:?}", config_manager.config());
         }
-        Some(CliCommand::Sync { remove_untracked }) => {
-            let pacman_installed = impaccable::pacman::query_explicitly_installed().context("Failed to query installed packages")?;
-
-            let target_config = config_manager.config().targets.get(active_target.target()).ok_or_else(|| anyhow!(impaccable::Error::TargetNotFound(active_target.target().clone())))?;
-            let should_be_installed : BTreeSet<&PackageId> = config_manager.package_config().packages_of_groups(&target_config.root_groups).collect();
+        Some(CliCommand::Report { snapshot_dir, format, min_machines }) => {
+            let snapshots = report::read_snapshots(snapshot_dir)?;
+            let fleet_report = FleetReport::new(&snapshots, config_manager.config(), config_manager.package_config(), *min_machines);
+            match format {
+                ReportFormat::Text => print!("{}", fleet_report),
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&fleet_report)?),
+                ReportFormat::Html => print!("{}", fleet_report.render_html()),
+            }
+        }
+        Some(CliCommand::Sync { remove_untracked, target: target_override, pacstrap }) => {
+            warn_about_repository_state(&config_manager);
 
-            let not_installed = should_be_installed.iter().filter(|package| !pacman_installed.contains(**package));
-            pacman::install_packages(not_installed).context("Failed to install missing packages")?;
-            
-            if *remove_untracked {
-                let untracked_packages = pacman_installed.iter().cloned().filter(|package| should_be_installed.contains(package));
-                let _uninstall_exit_status = pacman::uninstall_packages(untracked_packages)?;
+            let target_config = config_manager.target(target_override.as_ref().unwrap_or(active_target.target()))?;
+            let package_config = config_manager.target_package_config(target_config)?;
+            let package_dir = config_manager.absolute_package_dir()?;
+            if *pacstrap {
+                // a fresh system has nothing installed and no pacman database to query yet
+                let root = pacman_options.root.as_ref().expect("clap requires --root for --pacstrap");
+                let mut plan = Plan::new(&BTreeSet::new(), &package_config, target_config);
+                plan.plan_units(&package_config, target_config, |_| Ok(BTreeSet::new()))?;
+                plan.plan_hooks(&package_config, &package_dir);
+                plan.bootstrap(&runner, root, &systemctl_options)?;
+            } else {
+                let pacman_installed = impaccable::pacman::query_explicitly_installed(&runner, &pacman_options).context("Failed to query installed packages")?;
+                let mut plan = Plan::new(&pacman_installed, &package_config, target_config);
+                if *remove_untracked {
+                    plan.ignore_required(|packages| packages_required_by(&runner, &pacman_options, packages))?;
+                }
+                plan.plan_units(&package_config, target_config, |units| systemd::query_enabled(&runner, &systemctl_options, units))?;
+                plan.plan_hooks(&package_config, &package_dir);
+                let confirm : ConfirmRemoval = &confirm_removal;
+                plan.apply(&runner, &pacman_options, &systemctl_options, remove_untracked.then_some(confirm))?;
             }
         }
         Some(CliCommand::Add { packages, group }) => {
             let unique_packages : BTreeSet<PackageId> = packages.clone().into_iter().collect();
+            let added_count = unique_packages.len();
             config_manager.package_config_mut().add_packages(unique_packages, group).context("Failed to add packages")?;
             println!("Added the following packages{:?}", packages);
+            commit_message = Some(format!("add: add {} to group {}", package_count(added_count), group));
         }
         Some(CliCommand::Remove { package, group }) => {
-            config_manager.package_config_mut().remove_package(package, group)?
+            // templated members are removed by their interpolated name as well
+            let member = variables::resolve_member(config_manager.config(), config_manager.package_config(), group, package);
+            config_manager.package_config_mut().remove_package(&member, group)?;
+            commit_message = Some(format!("remove: remove {} from group {}", package, group));
+        }
+        Some(CliCommand::Mv { packages, from, to, all }) => {
+            let packages : Option<BTreeSet<PackageId>> = if *all {
+                None
+            } else {
+                Some(packages.iter().map(|package| variables::resolve_member(config_manager.config(), config_manager.package_config(), from, package)).collect())
+            };
+            let moved = config_manager.package_config_mut().move_packages(packages, from, to).context("Failed to move packages")?;
+            println!("Moved the following packages from '{}' to '{}': {:?}", from, to, moved);
+            commit_message = Some(format!("mv: move {} from group {} to group {}", package_count(moved.len()), from, to));
         }
         Some(CliCommand::Target(subcommand)) => {
             match subcommand {
@@ -158,6 +256,103 @@ fn main() -> std::result::Result<(), anyhow::Error> {
                         println!("Did not set target '{}' because it is not in the list of available targets. Check targets with `target ls` or override with `--force`", target)
                     }
                 },
+                Target::Create { target, groups } => {
+                    let target_config = TargetConfig::from_root_groups(groups.iter().cloned().collect());
+                    config_manager.create_target(target.clone(), target_config).context("Failed to create target")?;
+                    println!("Created target '{}'", target);
+                    commit_message = Some(format!("target: create target {}", target));
+                },
+                Target::Delete { target, force } => {
+                    if target == active_target.target() && !*force {
+                        bail!("Target '{}' is the active target. Set another active target first or override with `--force`", target);
+                    }
+                    config_manager.delete_target(target).context("Failed to delete target")?;
+                    println!("Deleted target '{}'", target);
+                    commit_message = Some(format!("target: delete target {}", target));
+                },
+                Target::Rename { target, new_name } => {
+                    config_manager.rename_target(target, new_name.clone()).context("Failed to rename target")?;
+                    if target == active_target.target() {
+                        active_target.set_target(new_name.clone(), &active_target_path).context("Failed to update active target")?;
+                    }
+                    println!("Renamed target '{}' to '{}'", target, new_name);
+                    commit_message = Some(format!("target: rename target {} to {}", target, new_name));
+                },
+                Target::Copy { target, new_target } => {
+                    config_manager.copy_target(target, new_target.clone()).context("Failed to copy target")?;
+                    println!("Copied target '{}' to '{}'", target, new_target);
+                    commit_message = Some(format!("target: copy target {} to {}", target, new_target));
+                },
+                Target::Diff { targets, snapshot_a, snapshot_b } => {
+                    if targets.len() + usize::from(snapshot_a.is_some()) + usize::from(snapshot_b.is_some()) != 2 {
+                        bail!("Pass two sides to compare, each as a target or with `--snapshot-a` or `--snapshot-b`");
+                    }
+                    // sides not replaced by a snapshot take the targets in order
+                    let mut targets = targets.iter();
+                    let mut resolve = |snapshot_path: &Option<PathBuf>| -> anyhow::Result<(String, PackageGroups)> {
+                        let Some(snapshot_path) = snapshot_path else {
+                            let target_id = targets.next().expect("the number of sides was checked before");
+                            let target = config_manager.target(target_id)?;
+                            return Ok((target_id.clone(), compare::target_packages(&config_manager.target_package_config(target)?, target)));
+                        };
+                        let snapshot = Snapshot::read(snapshot_path)?;
+                        let target_id = snapshot.target.as_ref().or(snapshot.hostname.as_ref());
+                        let name = snapshot_path.to_string_lossy().into_owned();
+                        let Some(target) = target_id.and_then(|target_id| config_manager.config().targets.get(target_id)) else {
+                            return Ok((name, compare::snapshot_packages(&snapshot, config_manager.package_config(), None)));
+                        };
+                        Ok((name, compare::snapshot_packages(&snapshot, &config_manager.target_package_config(target)?, Some(target))))
+                    };
+                    let (a, packages_a) = resolve(snapshot_a)?;
+                    let (b, packages_b) = resolve(snapshot_b)?;
+                    let diff = PackageDiff::new(packages_a, packages_b);
+
+                    use colored::Colorize;
+                    println!("Shared ({}):", diff.shared.len());
+                    for (package, (groups_a, groups_b)) in &diff.shared {
+                        if groups_a == groups_b {
+                            println!("  {}{}", package, format_groups(groups_a));
+                        } else {
+                            println!("  {} ({}: {}, {}: {})", package, a, join_groups(groups_a), b, join_groups(groups_b));
+                        }
+                    }
+                    println!("Only in {} ({}):", a, diff.only_a.len());
+                    for (package, groups) in &diff.only_a {
+                        println!("{} {}{}", "-".red(), package.red(), format_groups(groups));
+                    }
+                    println!("Only in {} ({}):", b, diff.only_b.len());
+                    for (package, groups) in &diff.only_b {
+                        println!("{} {}{}", "+".green(), package.green(), format_groups(groups));
+                    }
+                },
+                Target::AddGroup { target, groups } => {
+                    let mut added_groups = Vec::new();
+                    for group in groups {
+                        if config_manager.add_root_group(target, group.clone())? {
+                            println!("Added group '{}' to target '{}'", group, target);
+                            added_groups.push(group.as_str());
+                        } else {
+                            println!("Group '{}' already is a root group of target '{}'", group, target);
+                        }
+                    }
+                    if !added_groups.is_empty() {
+                        commit_message = Some(format!("target: add groups {} to target {}", added_groups.join(", "), target));
+                    }
+                },
+                Target::RemoveGroup { target, groups } => {
+                    let mut removed_groups = Vec::new();
+                    for group in groups {
+                        if config_manager.remove_root_group(target, group)? {
+                            println!("Removed group '{}' from target '{}'", group, target);
+                            removed_groups.push(group.as_str());
+                        } else {
+                            println!("Group '{}' is not a root group of target '{}'", group, target);
+                        }
+                    }
+                    if !removed_groups.is_empty() {
+                        commit_message = Some(format!("target: remove groups {} from target {}", removed_groups.join(", "), target));
+                    }
+                },
             }
         }
         Some(CliCommand::Groups(subcommand)) => {
@@ -165,181 +360,748 @@ fn main() -> std::result::Result<(), anyhow::Error> {
                 Groups::Ls => {
                     config_manager.package_config().iter_groups().for_each(|(group_name, _)| println!("{}", group_name))
                 }
+                Groups::Show { group } => {
+                    let (file_path, package_group) = config_manager.package_config()
+                        .find_group(group)
+                        .ok_or_else(|| anyhow!(impaccable::Error::GroupNotFound { group: group.clone() }))?;
+
+                    println!("Group: {}", group);
+                    println!("File: {}", file_path.to_string_lossy());
+                    let targets : Vec<&String> = config_manager.config().targets_with_root_group(group).collect();
+                    if targets.is_empty() {
+                        println!("Targets: none");
+                    } else {
+                        println!("Targets: {}", targets.iter().map(|target| target.as_str()).collect::<Vec<_>>().join(", "));
+                    }
+                    println!("Members:");
+                    for package in &package_group.members {
+                        println!("  {}", package);
+                    }
+                }
+                Groups::Create { group, file } => {
+                    let file_path = config_manager.package_file_path(file)?;
+                    create_group_in_file(config_manager.package_config_mut(), group, &file_path).context("Failed to create group")?;
+                    println!("Created group '{}' in '{}'", group, file_path.to_string_lossy());
+                    commit_message = Some(format!("groups: create group {}", group));
+                }
+                Groups::Delete { group, force } => {
+                    let targets : Vec<&String> = config_manager.config().targets_with_root_group(group).collect();
+                    if !targets.is_empty() && !*force {
+                        bail!("Group '{}' is a root group of the targets {:?}. Remove it from those targets or override with `--force`", group, targets);
+                    }
+                    let affected_targets = config_manager.delete_group(group).context("Failed to delete group")?;
+                    println!("Deleted group '{}'", group);
+                    for target in affected_targets {
+                        println!("Removed group '{}' from target '{}'", group, target);
+                    }
+                    commit_message = Some(format!("groups: delete group {}", group));
+                }
+                Groups::Rename { group, new_name } => {
+                    config_manager.rename_group(group, new_name.clone()).context("Failed to rename group")?;
+                    println!("Renamed group '{}' to '{}'", group, new_name);
+                    commit_message = Some(format!("groups: rename group {} to {}", group, new_name));
+                }
+                Groups::Move { group, to_file } => {
+                    let file_path = config_manager.package_file_path(to_file)?;
+                    move_group_to_file(config_manager.package_config_mut(), group, &file_path).context("Failed to move group")?;
+                    println!("Moved group '{}' to '{}'", group, file_path.to_string_lossy());
+                    commit_message = Some(format!("groups: move group {} to {}", group, to_file.to_string_lossy()));
+                }
             }
         }
-        Some(CliCommand::Plan { remove_untracked }) => {
-            let pacman_installed = impaccable::pacman::query_explicitly_installed().context("Failed to query installed packages")?;
+        Some(CliCommand::Export(Export { command: None, target: Some(target), format })) => {
+            let target_config = config_manager.target(target)?;
+            let package_config = config_manager.target_package_config(target_config)?;
+            let packages : BTreeSet<&PackageId> = package_config.packages_of_groups(&target_config.root_groups).collect();
 
-            let target = config_manager.config().targets.get(active_target.target()).context(format!("Failed to find root group {} in config", active_target.target()))?;
+            let rendered = match format {
+                ExportFormat::Plain => export::render_plain(&packages),
+                ExportFormat::Pacstrap => export::render_pacstrap(Path::new("/mnt"), &packages),
+                ExportFormat::Archiso => export::render_archiso(target, &packages),
+                ExportFormat::ArchinstallJson => export::render_archinstall_json(&packages)?,
+            };
+            print!("{}", rendered);
+        }
+        Some(CliCommand::Export(Export { command: None, target: None, .. })) => {
+            unreachable!("clap requires the target if no export subcommand is passed")
+        }
+        Some(CliCommand::Export(Export { command: Some(subcommand), .. })) => {
+            match subcommand {
+                ExportCommand::Containerfile { target } => {
+                    let target_config = config_manager.target(target)?;
+                    let repository_packages = pacman::query_repository_packages(&runner, &pacman_options).context("Failed to query repository packages")?;
+                    let containerfile = Containerfile::new(target, target_config, &config_manager.target_package_config(target_config)?, &repository_packages);
 
-            println!("Active target: {}", active_target.target());
-            println!("Configured groups: {}", toml::to_string(target)?);
+                    print!("{}", containerfile);
+                    if !containerfile.excluded.is_empty() {
+                        eprintln!("The following packages are not available in the repositories and require an AUR helper:");
+                        for package in &containerfile.excluded {
+                            eprintln!("  {}", package);
+                        }
+                    }
+                }
+                ExportCommand::Pkgbuild { target, per_group, output, pkgver } => {
+                    let target_config = config_manager.target(target)?;
+                    let package_config = config_manager.target_package_config(target_config)?;
+                    let pkgver = match pkgver {
+                        Some(pkgver) => pkgver.clone(),
+                        None => config_pkgver(&config_manager)?,
+                    };
+                    if !export::is_valid_pkgver(&pkgver) {
+                        bail!("Invalid pkgver '{}', it may only contain alphanumeric characters, periods, underscores and plus signs", pkgver);
+                    }
+                    let pkgbuilds = if *per_group {
+                        Pkgbuild::per_group(target, target_config, &package_config, &pkgver)
+                    } else {
+                        vec![Pkgbuild::from_target(target, target_config, &package_config, &pkgver)]
+                    };
 
+                    match output {
+                        Some(output_dir) => {
+                            for pkgbuild in pkgbuilds {
+                                let package_dir = output_dir.join(&pkgbuild.pkgname);
+                                fs::create_dir_all(&package_dir).context("Failed to create PKGBUILD directory")?;
+                                let mut file = File::create(package_dir.join("PKGBUILD")).context("Failed to create PKGBUILD")?;
+                                write!(file, "{}", pkgbuild)?;
+                                println!("Wrote PKGBUILD of '{}' to '{}'", pkgbuild.pkgname, package_dir.to_string_lossy());
+                            }
+                        },
+                        None => {
+                            for pkgbuild in pkgbuilds {
+                                print!("{}", pkgbuild);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Some(CliCommand::Plan { remove_untracked, installed_from, target: target_override }) => {
+            let snapshot = installed_from.as_deref().map(Snapshot::read).transpose()?;
+            let pacman_installed = match &snapshot {
+                Some(snapshot) => snapshot.explicitly_installed(),
+                None => impaccable::pacman::query_explicitly_installed(&runner, &pacman_options).context("Failed to query installed packages")?,
+            };
+
+            let target_id = target_override.as_ref().unwrap_or(active_target.target());
+            let target = config_manager.target(target_id)?;
+
+            if let Some(snapshot) = &snapshot {
+                println!("Snapshot of: {}", snapshot.hostname.as_deref().unwrap_or("unknown host"));
+            }
+            if target_override.is_some() {
+                println!("Target: {}", target_id);
+            } else {
+                println!("Active target: {}", target_id);
+            }
+            println!("Configured groups: {}", toml::to_string(target)?);
 
-            let missing_on_system : Vec<(&GroupId, PackageGroup)> =
-                config_manager.package_config().filter_groups(&target.root_groups)
-                    .map(|(name, package_contents)|{
-                        let not_installed : BTreeSet<PackageId> = package_contents.members.clone()
-                            .into_iter()
-                            .filter(|package| !pacman_installed.contains(package)).collect();
-                        (name, PackageGroup::from_members(not_installed))
-                    })
-                    .collect();
+            let package_config = config_manager.target_package_config(target)?;
+            let mut plan = Plan::new(&pacman_installed, &package_config, target);
+            if *remove_untracked {
+                plan.ignore_required(|packages| match &snapshot {
+                    Some(snapshot) => Ok(snapshot.packages_required_by(packages)),
+                    None => packages_required_by(&runner, &pacman_options, packages),
+                })?;
+            }
+            // a snapshot does not record the units, so they are only planned for the system
+            if snapshot.is_none() {
+                plan.plan_units(&package_config, target, |units| systemd::query_enabled(&runner, &systemctl_options, units))?;
+            }
+            plan.plan_hooks(&package_config, &config_manager.absolute_package_dir()?);
                 
             use colored::Colorize;
 
             println!("Sync would install the following programs:");
 
-            for (group, missing_packages) in missing_on_system {
-                // only display groups that have missing packages
-                if !missing_packages.members.is_empty() {
-                    println!("{}", format!("From group '{}':", group).green());
-                    for pkg in missing_packages.members {
-                        println!("{} {}", "+".green(), &pkg.green())
-                    }
+            // only groups that have missing packages are contained in the plan
+            for (group, missing_packages) in &plan.missing {
+                println!("{}", format!("From group '{}':", group).green());
+                for pkg in &missing_packages.members {
+                    println!("{} {}", "+".green(), pkg.green())
                 }
             }
+            if !plan.enable.is_empty() {
+                println!("Sync would enable the following units:");
+                for unit in &plan.enable {
+                    println!("{} {}", "+".green(), unit.to_string().green())
+                }
+            }
+            for hook in plan.hooks.iter().filter(|hook| hook.kind == HookKind::PostInstall) {
+                println!("Sync would run the post_install hook of group '{}'", hook.group);
+            }
 
             if *remove_untracked {
                 println!("sync --remove-untracked would remove the following programs:");
 
-                let should_be_installed : BTreeSet<&PackageId> = config_manager.package_config().packages_of_groups(&target.root_groups).collect();
-                let untracked_packages : Vec<String> = pacman_installed.iter().cloned().filter(|package| !should_be_installed.contains(package)).collect();
-
-                for (untracked_package, required_by) in packages_required_by(untracked_packages)? {
-                    if required_by.is_empty() {
-                        println!("{} {}", "-".red(), untracked_package.red() )
+                for untracked_package in &plan.untracked {
+                    println!("{} {}", "-".red(), untracked_package.red() )
+                }
+                if !plan.disable.is_empty() {
+                    println!("sync --remove-untracked would disable the following units:");
+                    for unit in &plan.disable {
+                        println!("{} {}", "-".red(), unit.to_string().red())
                     }
                 }
+                for hook in plan.hooks.iter().filter(|hook| hook.kind == HookKind::PreRemove) {
+                    println!("sync --remove-untracked would run the pre_remove hook of group '{}'", hook.group);
+                }
             }
         }
 
-        Some(CliCommand::Template) => {
-            let system_configuration = impaccable::distro::get_system_configuration().context("Failed to get system configuration")?;
-            // TODO: switch to a display implementation
-            println!("Your system configuration: {}", &system_configuration);
-            let new_groups = impaccable::distro::generate_configuration(&system_configuration).context("Failed to template packages for your system configuration")?;
-            
-            let mut file_path = config_manager.absolute_package_dir()?;
-            file_path.push(system_configuration.distro);
-            file_path.set_extension("toml");
+        Some(CliCommand::Check) => {
+            // groups without target are never synced, their invalid members are only warned about
+            let (invalid_members, unused_invalid) : (Vec<_>, Vec<_>) = variables::find_invalid(config_manager.config(), config_manager.package_config())
+                .into_iter()
+                .partition(|invalid| invalid.target.is_some());
+            for invalid in &unused_invalid {
+                log::warn!("{} in '{}' of group '{}', which is not a root group of any target", invalid.error, invalid.member, invalid.group);
+            }
+            if invalid_members.is_empty() {
+                println!("No errors found");
+            } else {
+                for invalid in &invalid_members {
+                    println!("{} in '{}' of group '{}' (target '{}')", invalid.error, invalid.member, invalid.group, invalid.target.as_deref().unwrap_or_default());
+                }
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+
+        Some(CliCommand::Status) => {
+            let pacman_installed = impaccable::pacman::query_explicitly_installed(&runner, &pacman_options).context("Failed to query installed packages")?;
 
-            let mut file = File::create(file_path).context("Failed to create file for new package group")?;
-            let toml = toml::ser::to_string_pretty(&new_groups)?;
-            write!(file, "{}", toml)?;
+            let target = config_manager.target(active_target.target())?;
+            let package_config = config_manager.target_package_config(target)?;
+            let mut plan = Plan::new(&pacman_installed, &package_config, target);
+
+            // Untracked packages required by other packages are not removed by sync, so they do not count as drift
+            plan.ignore_required(|packages| packages_required_by(&runner, &pacman_options, packages))?;
+            plan.plan_units(&package_config, target, |units| systemd::query_enabled(&runner, &systemctl_options, units))?;
+            let missing_count = plan.missing_packages().len();
+
+            println!("Active target: {}", active_target.target());
+            println!("Missing: {}", missing_count);
+            println!("Untracked: {}", plan.untracked.len());
+            println!("Ignored (required by other packages): {}", plan.ignored.len());
+            println!("Units to enable: {}", plan.enable.len());
+
+            if missing_count == 0 && plan.untracked.is_empty() && plan.enable.is_empty() {
+                println!("In sync");
+            } else {
+                println!("Drifted");
+                return Ok(ExitCode::from(EXIT_STATUS_DRIFTED));
+            }
         }
 
-        Some(CliCommand::Import) => {
-            let pacman_installed = impaccable::pacman::query_explicitly_installed().context("Failed to query installed packages")?;
-            
-            let target = config_manager
-                .config()
-                .targets.get(active_target.target())
-                .context(format!("Failed to find active target '{}' in config", active_target.target()))?
-                .clone();
-
-            let should_be_installed : BTreeSet<&PackageId> = config_manager.package_config().packages_of_groups(&target.root_groups).collect();
-
-            let untracked_packages : Vec<String> = pacman_installed.iter().cloned().filter(|package| !should_be_installed.contains(package)).collect();
-
-            let Some(selected_package_indices) = MultiSelect::with_theme(&ColorfulTheme::default())
-                // BUG(low, ux, upstream?): prompt only shows on second page if paginated
-                // check if bug is fixable or provide dialog beforehand explaining what to do
-                .with_prompt("Select the packages you would like to import into your configuration")
-                .items(&untracked_packages)
-                .interact_opt()
-                .context("Package selection aborted")? else {
-                    bail!("Package selection aborted")
-            };
+        Some(CliCommand::Why { package }) => {
+            let pacman_installed = impaccable::pacman::query_explicitly_installed(&runner, &pacman_options).context("Failed to query installed packages")?;
+            if pacman_installed.contains(package) {
+                println!("'{}' is explicitly installed", package);
+            } else {
+                println!("'{}' is not explicitly installed", package);
+            }
 
-            if selected_package_indices.is_empty() {
-                return Ok(());
+            let declaring_groups = impaccable::explain::declaring_groups(config_manager.config(), config_manager.package_config(), package);
+            if declaring_groups.is_empty() {
+                println!("'{}' is not declared by any group", package);
+            }
+            for (file_path, group, targets) in declaring_groups {
+                let targets : Vec<&str> = targets.into_iter().map(|target| target.as_str()).collect();
+                if targets.is_empty() {
+                    println!("Declared by group '{}' in '{}', which is not a root group of any target", group, file_path.to_string_lossy());
+                } else {
+                    println!("Declared by group '{}' in '{}', root group of targets: {}", group, file_path.to_string_lossy(), targets.join(", "));
+                }
             }
 
-            let groups: Vec<&String> = config_manager.package_config().iter_groups().map(|(name, _)| name).collect();
+            let target = config_manager.target(active_target.target())?;
+            let package_config = config_manager.target_package_config(target)?;
+            let configured : BTreeSet<&PackageId> = package_config.packages_of_groups(&target.root_groups).collect();
+            if !configured.contains(package) {
+                let chains = impaccable::explain::dependency_chains(package, &configured, |packages| packages_required_by(&runner, &pacman_options, packages)).context("Failed to query dependants")?;
+                if chains.is_empty() {
+                    println!("'{}' is not required by any package configured for the active target '{}'", package, active_target.target());
+                } else {
+                    println!("Required by packages configured for the active target '{}':", active_target.target());
+                    for chain in chains {
+                        println!("  {}", chain.join(" <- "));
+                    }
+                }
+            }
+        }
 
-            let dialogue_theme = ColorfulTheme::default();
+        Some(CliCommand::Template(Template { command: Some(TemplateCommand::Diff { source }), .. })) => {
+            let registry = DistroRegistry::default();
+            let (distro_name, templated_groups) = generate_template(&registry, source, default_project_dirs.cache_dir())?;
 
-            let Some(group_selection) = FuzzySelect::with_theme(&dialogue_theme)
-                .with_prompt("Select the group to add the packages to")
-                .item("New group")
-                .items(&groups)
-                .interact_opt()
-                .context("File selection crashed")? else {
-                    bail!("File selection aborted")
-                };
+            let pacman_installed = impaccable::pacman::query_explicitly_installed(&runner, &pacman_options).context("Failed to query installed packages")?;
+            let template_diff = TemplateDiff::new(&pacman_installed, &templated_groups);
 
-            let group_id = 'group_sel: {
+            use colored::Colorize;
+            println!("{} defaults kept ({}):", distro_name, template_diff.kept.len());
+            for package in &template_diff.kept {
+                println!("  {}", package);
+            }
+            println!("{} defaults removed ({}):", distro_name, template_diff.removed.len());
+            for package in &template_diff.removed {
+                println!("{} {}", "-".red(), package.red());
+            }
+            println!("User added ({}):", template_diff.added.len());
+            for package in &template_diff.added {
+                println!("{} {}", "+".green(), package.green());
+            }
 
-                // Check if new group creation requested or can just use selected group
-                if group_selection != 0 {
-                    // requires decrement by 1 because "new group" item was prepended
-                    break 'group_sel groups[group_selection-1].to_string();
+            let target = config_manager.target(active_target.target())?.clone();
+            let package_config = config_manager.target_package_config(&target)?;
+            let configured : BTreeSet<&PackageId> = package_config.packages_of_groups(&target.root_groups).collect();
+            let untracked_added : Vec<PackageId> = template_diff.added.iter().filter(|package| !configured.contains(package)).cloned().collect();
+
+            if !untracked_added.is_empty() && Confirm::new()
+                .with_prompt(format!("{} user added packages are not tracked by the active target. Import them?", untracked_added.len()))
+                .interact()? {
+                    if let Some((group_id, imported_count)) = import_interactively(&mut config_manager, &active_target, &target, untracked_added)? {
+                        commit_message = Some(format!("template: import {} to group {}", package_count(imported_count), group_id));
+                    }
                 }
+        }
 
-                let new_group_name: String = Input::with_theme(&dialogue_theme)
-                    .with_prompt("Name for new group")
-                    // TODO(low, limitation): only ascii characters allowed by interact_text
-                    .interact_text().context("Failed to get new group name")?;
+        Some(CliCommand::Template(Template { command: None, list, source, strategy })) => {
+            let registry = DistroRegistry::default();
 
-                let file_paths : Vec<_> = config_manager.package_config().files.keys().collect();
-                let file_strs : Vec<_> = file_paths.iter().map(|path| path.to_string_lossy()).collect();
+            if *list {
+                for provider in registry.providers() {
+                    println!("{} ({}), desktops: {}", provider.id(), provider.name(), provider.desktops().join(", "));
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
 
-                let Some(file_selection) = FuzzySelect::with_theme(&dialogue_theme)
-                    .with_prompt("Select the file to store the group in")
-                    .item("New file")
-                    .items(&file_strs)
-                    .interact_opt()
-                    .context("File selection crashed")? else {
-                        bail!("File selection aborted")
-                    };
-                
-                let file_path = {
+            let (distro_name, new_groups) = generate_template(&registry, source, default_project_dirs.cache_dir())?;
 
-                    // Check if new file creation was selected or can just use selected file
-                    if file_selection != 0 {
-                        // requires decrement by 1 because "new file" item was prepended
-                        file_paths[file_selection-1].clone()
-                    } else {
-                        let new_file_name_rel : String = Input::with_theme(&dialogue_theme)
-                            .with_prompt(format!("New file name (stored inside package directory at '{}')", config_manager.absolute_package_dir()?.to_string_lossy()))
-                            .interact_text()
-                            .context("Failed to get new file name")?;
+            let file_path = config_manager.package_file_path(Path::new(&distro_name))?;
+            let Some(existing_file) = config_manager.package_config().files.get(&file_path) else {
+                config_manager.package_config_mut().create_file(&file_path, Some(new_groups)).context("Failed to create package file for template")?;
+                println!("Created '{}'", file_path.to_string_lossy());
+                auto_commit(&config_manager, &format!("template: create {} package file", distro_name));
+                return Ok(ExitCode::SUCCESS);
+            };
+
+            use colored::Colorize;
+            let dialogue_theme = ColorfulTheme::default();
+            let strategies = [MergeStrategy::Keep, MergeStrategy::Replace, MergeStrategy::Union];
 
+            let mut merged_groups = existing_file.groups.clone();
+            for (group_id, templated_group) in new_groups {
+                let Some(existing_group) = existing_file.groups.get(&group_id) else {
+                    println!("{}", format!("New group '{}' with {} packages", group_id, templated_group.members.len()).green());
+                    merged_groups.insert(group_id, templated_group);
+                    continue;
+                };
 
-                        let new_file_path = {
-                            let mut p = config_manager.absolute_package_dir()?; 
-                            p.push(new_file_name_rel);
-                            p.set_extension("toml");
-                            p
-                        };
-                        config_manager.package_config_mut().create_file(&new_file_path, None)?;
-                        new_file_path
+                let diff = GroupDiff::new(existing_group, &templated_group);
+                if diff.is_empty() {
+                    continue;
+                }
+                println!("Changes to group '{}':", group_id);
+                for package in &diff.added {
+                    println!("{} {}", "+".green(), package.green());
+                }
+                for package in &diff.removed {
+                    println!("{} {}", "-".red(), package.red());
+                }
+
+                let group_strategy = match strategy {
+                    Some(strategy) => *strategy,
+                    None => {
+                        let Some(selection) = Select::with_theme(&dialogue_theme)
+                            .with_prompt(format!("How should group '{}' be merged?", group_id))
+                            .items(&["keep existing group", "replace with template", "union of both"])
+                            .default(2)
+                            .interact_opt()
+                            .context("Merge strategy selection crashed")? else {
+                                bail!("Merge strategy selection aborted")
+                            };
+                        strategies[selection]
                     }
                 };
+                merged_groups.insert(group_id, impaccable::distro::merge_group(existing_group, &templated_group, group_strategy));
+            }
 
-                config_manager.package_config_mut().create_group(new_group_name.clone(), &file_path)?;
+            config_manager.package_config_mut().update_file(&file_path, merged_groups).context("Failed to update templated package file")?;
+            println!("Updated '{}'", file_path.to_string_lossy());
+            commit_message = Some(format!("template: update {} package file", distro_name));
+        }
 
-                new_group_name
+        Some(CliCommand::Import { group: Some(group_id), file, create, pattern, all, exclude, dry_run }) => {
+            if pattern.is_none() && !*all {
+                bail!("Select the packages to import with `--match` or `--all`");
+            }
+            let pattern = pattern.as_deref().map(Regex::new).transpose().context("Invalid package regex")?;
+            let exclude = exclude.iter().map(|glob| glob::Pattern::new(glob)).collect::<Result<Vec<_>, _>>().context("Invalid exclude glob")?;
+
+            let pacman_installed = impaccable::pacman::query_explicitly_installed(&runner, &pacman_options).context("Failed to query installed packages")?;
+            let target = config_manager.target(active_target.target())?;
+            let plan = Plan::new(&pacman_installed, &config_manager.target_package_config(target)?, target);
+
+            let selected_packages = plan.untracked_matching(pattern.as_ref(), &exclude);
+            let group_is_root_group = target.root_groups.contains(group_id);
+
+            let existing_file = config_manager.package_config().find_group(group_id).map(|(file_path, _)| file_path.clone());
+            let group_exists = existing_file.is_some();
+            let file_path = match file {
+                Some(file) => Some(config_manager.package_file_path(file)?),
+                None => None,
             };
+            if let (Some(existing_file), Some(file_path)) = (&existing_file, &file_path) {
+                if existing_file != file_path {
+                    bail!("Group '{}' already exists in '{}'. Move it with `groups move` or drop `--file`", group_id, existing_file.to_string_lossy());
+                }
+            }
+            if !group_exists && !*create {
+                bail!("Group '{}' does not exist. Pass `--create` to create it", group_id);
+            }
+            if !group_exists && file_path.is_none() {
+                bail!("Pass the file to create group '{}' in with `--file`", group_id);
+            }
+            if selected_packages.is_empty() {
+                println!("No untracked packages match the selection, nothing to import into group '{}'", group_id);
+                return Ok(ExitCode::SUCCESS);
+            }
 
-            let selected_packages: BTreeSet<String> = 
-                selected_package_indices
-                    .iter()
-                    .map(|index| untracked_packages[*index].clone())
-                    .collect();
-                config_manager.package_config_mut().add_packages(selected_packages, &group_id).context("Failed to add packages")?;
-
-            if !target.root_groups.contains(&group_id) {
-                let confirmation = Confirm::with_theme(&dialogue_theme)
-                    .with_prompt("The selected group is currently inactive. Add to active target's root groups?")
-                    .interact()
-                    .context("Confirmation aborted")?;
-                if confirmation {
-                    config_manager.add_root_group(active_target.target(), group_id)?;
+            if *dry_run {
+                if !group_exists {
+                    println!("Would create group '{}' in '{}'", group_id, file_path.unwrap_or_default().to_string_lossy());
+                }
+                println!("Would import the following packages into group '{}':", group_id);
+                for package in &selected_packages {
+                    println!("+ {}", package);
                 }
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            config_manager.package_config_mut().transaction(|package_config| {
+                if !group_exists {
+                    let file_path = file_path.expect("File path was checked before");
+                    if !package_config.files.contains_key(&file_path) {
+                        package_config.create_file(&file_path, None)?;
+                    }
+                    package_config.create_group(group_id.clone(), &file_path)?;
+                }
+                package_config.add_packages(selected_packages.iter().cloned(), group_id)
+            }).context("Failed to import packages")?;
+
+            println!("Imported {} packages into group '{}'", selected_packages.len(), group_id);
+            commit_message = Some(format!("import: add {} to group {}", package_count(selected_packages.len()), group_id));
+            if !group_is_root_group {
+                println!("Group '{}' is not a root group of the active target. Add it with `target add-group {} {}`", group_id, active_target.target(), group_id);
             }
         }
+
+        Some(CliCommand::Import { group: None, .. }) => {
+            let pacman_installed = impaccable::pacman::query_explicitly_installed(&runner, &pacman_options).context("Failed to query installed packages")?;
+            
+            let target = config_manager.target(active_target.target())?.clone();
+
+            let package_config = config_manager.target_package_config(&target)?;
+            let should_be_installed : BTreeSet<&PackageId> = package_config.packages_of_groups(&target.root_groups).collect();
+
+            let untracked_packages : Vec<String> = pacman_installed.iter().filter(|package| !should_be_installed.contains(package)).cloned().collect();
+
+            if let Some((group_id, imported_count)) = import_interactively(&mut config_manager, &active_target, &target, untracked_packages)? {
+                commit_message = Some(format!("import: add {} to group {}", package_count(imported_count), group_id));
+            }
+        }
+    }
+
+    if let Some(commit_message) = commit_message {
+        auto_commit(&config_manager, &commit_message);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Commits the changes to the configuration if auto-commit is enabled and the config directory is a git repository.
+/// Only the config file and package directory are committed, so e.g. the active target stays local.
+/// The changes are already written at this point, so a failing commit is only warned about and the command still succeeds.
+fn auto_commit(config_manager: &ConfigManager, message: &str) {
+    if let Err(err) = commit_config(config_manager, message) {
+        log::warn!("Failed to auto-commit '{}', the changes are left uncommitted: {:#}", message, err);
+    }
+}
+
+fn commit_config(config_manager: &ConfigManager, message: &str) -> anyhow::Result<()> {
+    if !config_manager.config().git.auto_commit {
+        return Ok(());
+    }
+    let config_dir = config_manager.config_dir()?;
+    if !git::is_repository(config_dir) {
+        log::warn!("Auto-commit is enabled, but '{}' is not a git repository", config_dir.to_string_lossy());
+        return Ok(());
+    }
+    let tracked_paths = config_manager.tracked_paths()?;
+    let tracked_paths : Vec<&Path> = tracked_paths.iter().map(PathBuf::as_path).collect();
+    if git::commit(config_dir, &tracked_paths, message).context("Failed to commit changes")? {
+        log::info!("Committed '{}'", message);
     }
     Ok(())
 }
+
+/// Derives the pkgver of exported metapackages from the config repository: the number of commits changing the configuration.
+fn config_pkgver(config_manager: &ConfigManager) -> anyhow::Result<String> {
+    let config_dir = config_manager.config_dir()?;
+    if !git::is_repository(config_dir) {
+        bail!("'{}' is not a git repository, pass the version of the metapackages with `--pkgver`", config_dir.to_string_lossy());
+    }
+    let tracked_paths = config_manager.tracked_paths()?;
+    let tracked_paths : Vec<&Path> = tracked_paths.iter().map(PathBuf::as_path).collect();
+    if git::status(config_dir).is_ok_and(|status| !status.uncommitted.is_empty()) {
+        log::warn!("The config repository has uncommitted changes, which do not increase the pkgver until they are committed");
+    }
+    Ok(git::commit_count(config_dir, &tracked_paths).context("Failed to count the commits of the config repository")?.to_string())
+}
+
+/// Warns if the configuration is tracked in a git repository with uncommitted or unpulled changes,
+/// as the system would then be synced with a configuration that differs from the shared one.
+fn warn_about_repository_state(config_manager: &ConfigManager) {
+    let Ok(config_dir) = config_manager.config_dir() else {
+        return;
+    };
+    if !git::is_repository(config_dir) {
+        return;
+    }
+    match git::status(config_dir) {
+        Ok(status) => {
+            if !status.uncommitted.is_empty() {
+                log::warn!("The config repository has {} uncommitted changes, check them with `impaccable git status`", status.uncommitted.len());
+            }
+            if let Some(behind @ 1..) = status.behind {
+                log::warn!("The config repository is {} commits behind its upstream, update it with `impaccable git pull`", behind);
+            }
+        },
+        Err(err) => log::warn!("Failed to check the state of the config repository: {:#}", err),
+    }
+}
+
+/// Formats the groups responsible for a package as a parenthesized suffix, empty if there are none.
+fn format_groups(groups: &BTreeSet<GroupId>) -> String {
+    if groups.is_empty() { String::new() } else { format!(" ({})", join_groups(groups)) }
+}
+
+fn join_groups(groups: &BTreeSet<GroupId>) -> String {
+    if groups.is_empty() { String::from("no group") } else { groups.iter().map(|group| group.as_str()).collect::<Vec<_>>().join(", ") }
+}
+
+/// Formats a package count for messages, e.g. "1 package" or "3 packages".
+fn package_count(count: usize) -> String {
+    if count == 1 { String::from("1 package") } else { format!("{} packages", count) }
+}
+
+/// Creates a group in the package file at `file_path`, creating the file if it does not exist yet.
+/// Runs as a transaction, so no empty file is left behind if the group cannot be created.
+fn create_group_in_file(package_config: &mut PackageConfiguration, group_id: &GroupId, file_path: &Path) -> impaccable::Result<()> {
+    package_config.transaction(|package_config| {
+        if !package_config.files.contains_key(file_path) {
+            package_config.create_file(file_path, None)?;
+        }
+        package_config.create_group(group_id.clone(), file_path)
+    })
+}
+
+/// Moves a group to the package file at `file_path`, creating the file if it does not exist yet.
+/// Runs as a transaction, so no empty file is left behind if the group cannot be moved.
+fn move_group_to_file(package_config: &mut PackageConfiguration, group_id: &GroupId, file_path: &Path) -> impaccable::Result<()> {
+    package_config.transaction(|package_config| {
+        if !package_config.files.contains_key(file_path) {
+            package_config.create_file(file_path, None)?;
+        }
+        package_config.move_group(group_id, file_path)
+    })
+}
+
+/// Selects the distro provider and generates the template groups for the system.
+/// Returns the name of the distro along with the groups.
+fn generate_template(registry: &DistroRegistry, source_args: &TemplateSourceArgs, cache_dir: &Path) -> anyhow::Result<(String, PackageGroupMap)> {
+    let provider = match &source_args.distro {
+        Some(distro) => registry.get(distro).with_context(|| format!("Unknown distro provider '{}'. List the available providers with `template --list`", distro))?,
+        None => registry.detect_system()?,
+    };
+
+    let system_configuration = impaccable::distro::get_system_configuration(provider, source_args.desktop.as_deref()).context("Failed to get system configuration")?;
+    println!("Your system configuration: {}", &system_configuration);
+    if system_configuration.desktop.is_none() && !provider.desktops().is_empty() {
+        println!("No supported desktop detected, only templating the base packages. Select a desktop with `--desktop`");
+    }
+
+    let cache_dir = if source_args.no_cache { None } else { Some(cache_dir.join("templates")) };
+    let timeout = Duration::from_secs(source_args.timeout);
+    let template_source = match &source_args.source {
+        Some(source) => TemplateSource::parse(source, cache_dir, timeout),
+        None => TemplateSource::Http(HttpSource { base_url: None, cache_dir, timeout }),
+    };
+    let groups = provider.generate_groups(&system_configuration, &template_source).context("Failed to template packages for your system configuration")?;
+    Ok((system_configuration.distro, groups))
+}
+
+/// Lists what sync --remove-untracked changes and asks for confirmation, so pacman can uninstall without prompting after the hooks ran.
+fn confirm_removal(plan: &Plan) -> anyhow::Result<bool> {
+    use colored::Colorize;
+
+    if !plan.disable.is_empty() {
+        println!("The following units will be disabled:");
+        for unit in &plan.disable {
+            println!("{} {}", "-".red(), unit.to_string().red())
+        }
+    }
+    if !plan.untracked.is_empty() {
+        println!("The following untracked packages will be removed:");
+        for package in &plan.untracked {
+            println!("{} {}", "-".red(), package.red())
+        }
+    }
+    for hook in plan.hooks.iter().filter(|hook| hook.kind == HookKind::PreRemove) {
+        println!("The pre_remove hook of group '{}' will run first", hook.group);
+    }
+    Confirm::new()
+        .with_prompt("Proceed with the removal?")
+        .default(false)
+        .interact()
+        .context("Confirmation aborted")
+}
+
+/// Lets the user select packages from `candidates` and a group to import them into, creating the group if requested.
+/// Offers to add the group to the root groups of the active target if it is not one already.
+/// Returns the group the packages were imported into and the number of imported packages, `None` if no packages were selected.
+fn import_interactively(config_manager: &mut ConfigManager, active_target: &ActiveTarget, target: &TargetConfig, candidates: Vec<PackageId>) -> anyhow::Result<Option<(GroupId, usize)>> {
+    let Some(selected_package_indices) = MultiSelect::with_theme(&ColorfulTheme::default())
+        // BUG(low, ux, upstream?): prompt only shows on second page if paginated
+        // check if bug is fixable or provide dialog beforehand explaining what to do
+        .with_prompt("Select the packages you would like to import into your configuration")
+        .items(&candidates)
+        .interact_opt()
+        .context("Package selection aborted")? else {
+            bail!("Package selection aborted")
+    };
+
+    if selected_package_indices.is_empty() {
+        return Ok(None);
+    }
+
+    let groups: Vec<&String> = config_manager.package_config().iter_groups().map(|(name, _)| name).collect();
+
+    let dialogue_theme = ColorfulTheme::default();
+
+    let Some(group_selection) = FuzzySelect::with_theme(&dialogue_theme)
+        .with_prompt("Select the group to add the packages to")
+        .item("New group")
+        .items(&groups)
+        .interact_opt()
+        .context("File selection crashed")? else {
+            bail!("File selection aborted")
+        };
+
+    let group_id = 'group_sel: {
+
+        // Check if new group creation requested or can just use selected group
+        if group_selection != 0 {
+            // requires decrement by 1 because "new group" item was prepended
+            break 'group_sel groups[group_selection-1].to_string();
+        }
+
+        let new_group_name: String = Input::with_theme(&dialogue_theme)
+            .with_prompt("Name for new group")
+            // TODO(low, limitation): only ascii characters allowed by interact_text
+            .interact_text().context("Failed to get new group name")?;
+
+        let file_paths : Vec<_> = config_manager.package_config().files.keys().collect();
+        let file_strs : Vec<_> = file_paths.iter().map(|path| path.to_string_lossy()).collect();
+
+        let Some(file_selection) = FuzzySelect::with_theme(&dialogue_theme)
+            .with_prompt("Select the file to store the group in")
+            .item("New file")
+            .items(&file_strs)
+            .interact_opt()
+            .context("File selection crashed")? else {
+                bail!("File selection aborted")
+            };
+        
+        let file_path = {
+
+            // Check if new file creation was selected or can just use selected file
+            if file_selection != 0 {
+                // requires decrement by 1 because "new file" item was prepended
+                file_paths[file_selection-1].clone()
+            } else {
+                let new_file_name_rel : String = Input::with_theme(&dialogue_theme)
+                    .with_prompt(format!("New file name (stored inside package directory at '{}')", config_manager.absolute_package_dir()?.to_string_lossy()))
+                    .interact_text()
+                    .context("Failed to get new file name")?;
+
+
+                let new_file_path = config_manager.package_file_path(Path::new(&new_file_name_rel))?;
+                config_manager.package_config_mut().create_file(&new_file_path, None)?;
+                new_file_path
+            }
+        };
+
+        config_manager.package_config_mut().create_group(new_group_name.clone(), &file_path)?;
+
+        new_group_name
+    };
+
+    let selected_packages: BTreeSet<String> = 
+        selected_package_indices
+            .iter()
+            .map(|index| candidates[*index].clone())
+            .collect();
+    let imported_count = selected_packages.len();
+    config_manager.package_config_mut().add_packages(selected_packages, &group_id).context("Failed to add packages")?;
+
+    if !target.root_groups.contains(&group_id) {
+        let confirmation = Confirm::with_theme(&dialogue_theme)
+            .with_prompt("The selected group is currently inactive. Add to active target's root groups?")
+            .interact()
+            .context("Confirmation aborted")?;
+        if confirmation {
+            config_manager.add_root_group(active_target.target(), group_id.clone())?;
+        }
+    }
+    Ok(Some((group_id, imported_count)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_group_in_file_rollback() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base_file = temp_dir.path().join("base.toml");
+        let new_file = temp_dir.path().join("new.toml");
+        let mut package_config = PackageConfiguration::default();
+        package_config.create_file(&base_file, None).unwrap();
+        package_config.create_group("tools".to_owned(), &base_file).unwrap();
+
+        // the group already exists in another file
+        assert!(create_group_in_file(&mut package_config, &"tools".to_owned(), &new_file).is_err());
+        assert!(!new_file.exists());
+        assert!(!package_config.files.contains_key(&new_file));
+
+        create_group_in_file(&mut package_config, &"dev".to_owned(), &new_file).unwrap();
+        assert_eq!(&new_file, package_config.find_group(&"dev".to_owned()).unwrap().0);
+    }
+
+    #[test]
+    fn test_move_group_to_file_rollback() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base_file = temp_dir.path().join("base.toml");
+        let new_file = temp_dir.path().join("new.toml");
+        let mut package_config = PackageConfiguration::default();
+        package_config.create_file(&base_file, None).unwrap();
+        package_config.create_group("tools".to_owned(), &base_file).unwrap();
+
+        assert!(move_group_to_file(&mut package_config, &"nope".to_owned(), &new_file).is_err());
+        assert!(!new_file.exists());
+        assert!(!package_config.files.contains_key(&new_file));
+
+        move_group_to_file(&mut package_config, &"tools".to_owned(), &new_file).unwrap();
+        assert_eq!(&new_file, package_config.find_group(&"tools".to_owned()).unwrap().0);
+    }
+}
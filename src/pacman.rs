@@ -0,0 +1,414 @@
+use std::{collections::{BTreeMap, BTreeSet}, process::{Command, Stdio}, ffi::OsStr, path::{Path, PathBuf}, sync::OnceLock};
+
+use anyhow::{Context, bail};
+use pomsky_macro::pomsky;
+use regex::Regex;
+
+use super::runner::CommandRunner;
+
+const RE_PACKAGE_REQUIRED_BY: &str = pomsky!(
+    let package_name_char = ['a'-'z' '0'-'9' '@' '.' '_' '+' '-'];
+    "Required By"[s]+": ":(((package_name_char+)' '*)+ | "None")
+);
+
+/// Ensures the contained regex is only compiled once to avoid performance impact in loops.
+/// Thread-safe due to usage of OnceLock
+fn re_package_required_by() -> &'static Regex {
+    static RE : OnceLock<Regex> = OnceLock::new();
+    // There's a test ensuring this unwrap never panics: tests::test_required_by_regex_valid
+    RE.get_or_init(|| Regex::new(RE_PACKAGE_REQUIRED_BY).unwrap())
+}
+
+/// Selects the system pacman operates on, e.g. a mounted disk to bootstrap.
+/// The default operates on the running system.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PacmanOptions {
+    /// Installation root, passed as `--root`
+    pub root: Option<PathBuf>,
+    /// Database location, passed as `--dbpath`
+    pub dbpath: Option<PathBuf>,
+    /// Root pacman chroots into before operating, passed as `--sysroot`. Also used for its configuration
+    pub sysroot: Option<PathBuf>,
+}
+
+impl PacmanOptions {
+    /// Creates a pacman command with the options applied.
+    fn command(&self) -> Command {
+        let mut command = Command::new("pacman");
+        if let Some(sysroot) = &self.sysroot {
+            command.arg("--sysroot").arg(sysroot);
+        }
+        if let Some(root) = &self.root {
+            command.arg("--root").arg(root);
+        }
+        if let Some(dbpath) = &self.dbpath {
+            command.arg("--dbpath").arg(dbpath);
+        }
+        command
+    }
+
+    /// Root of the system pacman operates on, `root` is relative to `sysroot` if both are set. `None` for the running system
+    pub fn system_root(&self) -> Option<PathBuf> {
+        match (&self.sysroot, &self.root) {
+            (Some(sysroot), Some(root)) => Some(sysroot.join(root.strip_prefix("/").unwrap_or(root))),
+            (sysroot, root) => root.clone().or_else(|| sysroot.clone()),
+        }
+    }
+}
+
+/// Queries what packages are installed on the system
+pub fn query_explicitly_installed(runner: &dyn CommandRunner, options: &PacmanOptions) -> anyhow::Result<BTreeSet<String>> {
+    let mut command = options.command();
+    command.arg("-Qqe");
+    let pacman_output_bytes = runner
+        .output(&mut command)
+        .context("Failed to run pacman -Qqe")?
+        .stdout;
+    let pacman_output_string = String::from_utf8(pacman_output_bytes).context("Failed to parse pacman stdout as utf8")?;
+    let mut installed_set : BTreeSet<String> = BTreeSet::new();
+    for line in pacman_output_string.lines() {
+        installed_set.insert(line.to_owned());
+    }
+    log::trace!("Parsed {} explicitly installed packages", installed_set.len());
+    Ok(installed_set)
+}
+
+/// The official repositories, the only ones enabled in the `archlinux` container images
+pub const OFFICIAL_REPOSITORIES : [&str; 3] = ["core", "extra", "multilib"];
+
+/// Queries what can be installed from the official repositories: the names of the packages, the names they provide and their groups
+pub fn query_repository_packages(runner: &dyn CommandRunner, options: &PacmanOptions) -> anyhow::Result<BTreeSet<String>> {
+    let mut command = options.command();
+    // the field names of the parsed output are localized
+    command.arg("-Si").env("LC_ALL", "C");
+    let output = runner
+        .output(&mut command)
+        .context("Failed to run pacman -Si")?;
+    if !output.status.success() {
+        bail!("pacman -Si exited with {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let pacman_output_string = String::from_utf8(output.stdout).context("Failed to parse pacman stdout as utf8")?;
+    let repository_packages = parse_repository_packages(&pacman_output_string, &OFFICIAL_REPOSITORIES);
+    log::trace!("Parsed {} repository packages, provides and groups", repository_packages.len());
+    Ok(repository_packages)
+}
+
+/// Parses the names, provides and groups of the packages of `repositories` from the output of `pacman -Si`.
+/// Versions of provides, e.g. `sh=5.2`, are stripped.
+fn parse_repository_packages(pacman_output: &str, repositories: &[&str]) -> BTreeSet<String> {
+    let mut repository_packages = BTreeSet::new();
+    // packages are separated by empty lines
+    for package_info in pacman_output.split("\n\n") {
+        let mut fields : BTreeMap<&str, String> = BTreeMap::new();
+        let mut current_field = None;
+        for line in package_info.lines() {
+            match line.split_once(" : ") {
+                Some((field, value)) if !line.starts_with(' ') => {
+                    let field = field.trim_end();
+                    fields.insert(field, value.trim().to_owned());
+                    current_field = Some(field);
+                },
+                // continuation of a wrapped list
+                _ => if let Some(value) = current_field.and_then(|field| fields.get_mut(field)) {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                },
+            }
+        }
+        if !fields.get("Repository").is_some_and(|repository| repositories.contains(&repository.as_str())) {
+            continue;
+        }
+        if let Some(name) = fields.get("Name") {
+            repository_packages.insert(name.clone());
+        }
+        for field in ["Provides", "Groups"] {
+            let names = fields.get(field).map(|value| value.split_whitespace()).into_iter().flatten();
+            repository_packages.extend(names
+                .filter(|name| *name != "None")
+                .map(|name| name.split(['=', '<', '>']).next().unwrap_or(name).to_owned()));
+        }
+    }
+    repository_packages
+}
+
+/// Queries the versions of the explicitly installed packages
+pub fn query_explicitly_installed_versions(runner: &dyn CommandRunner, options: &PacmanOptions) -> anyhow::Result<BTreeMap<String, String>> {
+    query_versions(runner, options, "-Qe")
+}
+
+/// Queries the versions of the packages installed as dependencies
+pub fn query_dependency_versions(runner: &dyn CommandRunner, options: &PacmanOptions) -> anyhow::Result<BTreeMap<String, String>> {
+    query_versions(runner, options, "-Qd")
+}
+
+fn query_versions(runner: &dyn CommandRunner, options: &PacmanOptions, query: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut command = options.command();
+    command.arg(query);
+    let output = runner
+        .output(&mut command)
+        .with_context(|| format!("Failed to run pacman {}", query))?;
+    // pacman exits with 1 if no package matches the query
+    if !output.status.success() && !output.stderr.is_empty() {
+        bail!("pacman {} failed: {}", query, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let pacman_output_string = String::from_utf8(output.stdout).context("Failed to parse pacman stdout as utf8")?;
+    parse_versions(&pacman_output_string)
+}
+
+/// Parses the `name version` lines printed by pacman queries.
+fn parse_versions(pacman_output: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    pacman_output
+        .lines()
+        .map(|line| {
+            line.split_once(' ')
+                .map(|(package, version)| (package.to_owned(), version.to_owned()))
+                .with_context(|| format!("Failed to parse pacman output line '{}'", line))
+        })
+        .collect()
+}
+
+/// Installs the supplied packages.
+pub fn install_packages<I, S>(runner: &dyn CommandRunner, options: &PacmanOptions, packages: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = options.command();
+    command.arg("-S").args(packages);
+    let status = runner
+        .status(command.stdin(Stdio::inherit()))
+        .context("Failed to run pacman")?;
+    if !status.success() {
+        bail!("pacman -S exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Bootstraps a new system at `root` with the supplied packages using pacstrap, which also sets up the
+/// pacman database and keyring that installing with `--root` requires.
+pub fn pacstrap_packages<I, S>(runner: &dyn CommandRunner, root: &Path, packages: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = Command::new("pacstrap");
+    command.arg("-K").arg(root).args(packages);
+    let status = runner
+        .status(command.stdin(Stdio::inherit()))
+        .context("Failed to run pacstrap")?;
+    if !status.success() {
+        bail!("pacstrap exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Uninstalls the supplied packages without prompting, the removal has to be confirmed before.
+pub fn uninstall_packages<I, S>(runner: &dyn CommandRunner, options: &PacmanOptions, packages: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = options.command();
+    command.arg("-Rs").arg("--noconfirm").args(packages);
+    let status = runner
+        .status(command.stdin(Stdio::inherit())).context("Failed to run pacman -Rs")?;
+    if !status.success() {
+        bail!("pacman -Rs exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Gets the packages requiring the passed packages.
+/// indicating no packages requiring the given package.
+/// TODO(low, api): consider returning map instead (mapping package name -> dependants)
+pub fn packages_required_by(runner: &dyn CommandRunner, options: &PacmanOptions, packages: Vec<String>) -> anyhow::Result<Vec<(String, Vec<String>)>> {
+    let mut command = options.command();
+    command.arg("-Qi").args(&packages);
+    let pacman_output_bytes = runner
+        .output(&mut command)
+        .context("Failed to run pacman -Qi")?
+        .stdout;
+
+    // TODO(low, cross-platform): use OsStr instead - effectively not a problem on linux because strings are utf-8, but more idiomatic
+    let pacman_output_str = std::str::from_utf8(&pacman_output_bytes).context("Failed to parse pacman stdout as utf8")?;
+    let dependants = parse_required_by_many(pacman_output_str, Some(packages.len()))?;
+
+    Ok(packages.into_iter().zip(dependants).collect())
+}
+
+/// Parses the `Required By` attribute of the pacman output for many packages.
+/// Optionally takes the package count for vec init optimization
+fn parse_required_by_many(pacman_output: &str, opt_package_count: Option<usize>) -> anyhow::Result<Vec<Vec<String>>> {
+    let mut result = Vec::with_capacity(opt_package_count.unwrap_or(0));
+
+    // separate into the chunks for each package
+    // skips the first empty part because the data starts with the "delimiter"
+    for single_package_chunkj in pacman_output.split("Name").skip(1) {
+        let package_dependents = parse_required_by(single_package_chunkj)?;
+        log::trace!("Parsed dependants {:?}", package_dependents);
+        result.push(package_dependents);
+    }
+    Ok(result)
+}
+
+/// Parses the `Required By` attribute of the pacman output for a single package.
+/// Packages with a "None" value will return and empty Vec.
+fn parse_required_by(pacman_output: &str) -> anyhow::Result<Vec<String>> {
+    let re = re_package_required_by();
+    let Some(caps) = re.captures(pacman_output) else {
+        bail!("No matches for 'Required by'");
+    };
+
+    let dependants : Vec<String> =
+        caps
+            .get(1)
+            .context("Failed to get capturing group 1")?
+            .as_str()
+            .split_whitespace()
+            .map(|s| s.to_owned())
+            .collect();
+    
+    // Packages without dependencies have a "None" value.
+    if let Some(first_dep) = dependants.first() {
+        if first_dep == "None" {
+            return Ok(Vec::new())
+        }
+    }
+
+    Ok(dependants)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // these become hard to read as all caps, only used in tests
+    #[allow(non_upper_case_globals)]
+    const pacman_Qi_output_xorg : &str =
+r#"Name            : xorg-server
+Version         : 21.1.8-2
+Description     : Xorg X server
+Architecture    : x86_64
+URL             : https://xorg.freedesktop.org
+Licenses        : custom
+Groups          : xorg
+Provides        : X-ABI-VIDEODRV_VERSION=25.2  X-ABI-XINPUT_VERSION=24.4  X-ABI-EXTENSION_VERSION=10.0  x-server
+Depends On      : libepoxy  libxfont2  pixman  xorg-server-common  libunwind  dbus  libgl  xf86-input-libinput  nettle  libpciaccess  libdrm  libxshmfence
+                    libxcvt
+Optional Deps   : None
+Required By     : lightdm  lightdm-slick-greeter
+Optional For    : None
+Conflicts With  : nvidia-utils<=331.20  glamor-egl  xf86-video-modesetting
+Replaces        : glamor-egl  xf86-video-modesetting
+Installed Size  : 3,73 MiB
+Packager        : Laurent Carlier <lordheavym@archlinux.org>
+Build Date      : Mo 10 Jul 2023 11:25:36 CEST
+Install Date    : Mi 12 Jul 2023 17:24:40 CEST
+Install Reason  : Explicitly installed
+Install Script  : Yes
+Validated By    : Signature
+"#;
+
+    #[allow(non_upper_case_globals)]
+    const pacman_Qi_output_lightdm_slick : &str =
+r#"Name            : eos-lightdm-slick-theme
+Version         : 3.2-1
+Description     : EndeavourOS theme for lightdm-slick-greeter
+Architecture    : any
+URL             : https://www.endeavouros.com
+Licenses        : GPL3
+Groups          : None
+Provides        : None
+Depends On      : lightdm  lightdm-slick-greeter
+Optional Deps   : eos-qogir-icons [installed]
+                  arc-gtk-theme-eos [installed]
+Required By     : None
+Optional For    : None
+Conflicts With  : None
+Replaces        : None
+Installed Size  : 382,00 B
+Packager        : EndeavourOS <info@endeavouros.com>
+Build Date      : Mi 21 Dez 2022 14:48:28 CET
+Install Date    : Fr 03 Feb 2023 14:51:35 CET
+Install Reason  : Explicitly installed
+Install Script  : Yes
+Validated By    : Signature
+"#;
+
+    #[test]
+    fn test_required_by_regex_valid() {
+        Regex::new(RE_PACKAGE_REQUIRED_BY).unwrap();
+    }
+
+    #[test]
+    fn test_parse_repository_packages() {
+        let pacman_output = r#"Repository      : core
+Name            : bash
+Version         : 5.2.037-5
+Provides        : sh  libhistory.so=8-64  libreadline.so=8-64
+Depends On      : readline  libreadline.so=8-64  glibc  ncurses
+Groups          : None
+
+Repository      : extra
+Name            : plasma-desktop
+Version         : 6.4.5-1
+Provides        : None
+Groups          : plasma
+Optional Deps   : plasma-nm: Network manager applet
+                  plasma-pa: Audio volume applet
+
+Repository      : chaotic-aur
+Name            : paru
+Version         : 2.0.4-1
+Provides        : paru-bin
+Groups          : None
+"#;
+        let expected : BTreeSet<String> = ["bash", "libhistory.so", "libreadline.so", "plasma", "plasma-desktop", "sh"]
+            .map(|name| name.to_owned())
+            .into();
+        assert_eq!(expected, parse_repository_packages(pacman_output, &OFFICIAL_REPOSITORIES));
+    }
+
+    #[test]
+    fn test_options_command() {
+        let options = PacmanOptions { root: Some("/mnt".into()), dbpath: None, sysroot: Some("/srv/chroot".into()) };
+        let command = options.command();
+        let args : Vec<&OsStr> = command.get_args().collect();
+        assert_eq!(vec!["--sysroot", "/srv/chroot", "--root", "/mnt"], args);
+        assert_eq!(0, PacmanOptions::default().command().get_args().count());
+    }
+
+    #[test]
+    fn test_options_system_root() {
+        let system_root = |root: Option<&str>, sysroot: Option<&str>| PacmanOptions { root: root.map(PathBuf::from), dbpath: None, sysroot: sysroot.map(PathBuf::from) }.system_root();
+        assert_eq!(None, system_root(None, None));
+        assert_eq!(Some(PathBuf::from("/mnt")), system_root(Some("/mnt"), None));
+        assert_eq!(Some(PathBuf::from("/srv/chroot")), system_root(None, Some("/srv/chroot")));
+        assert_eq!(Some(PathBuf::from("/srv/chroot/mnt")), system_root(Some("/mnt"), Some("/srv/chroot")));
+    }
+
+    #[test]
+    fn test_parse_versions() {
+        let versions = parse_versions("bat 0.24.0-1\nlightdm 1:1.32.0-4\n").unwrap();
+        let expected : BTreeMap<String, String> = [
+            ("bat".to_owned(), "0.24.0-1".to_owned()),
+            ("lightdm".to_owned(), "1:1.32.0-4".to_owned()),
+        ].into();
+        assert_eq!(expected, versions);
+        assert!(parse_versions("bat").is_err());
+    }
+
+    #[test]
+    fn test_parse_required_by() {
+        {
+            let parsed_dependants = parse_required_by(pacman_Qi_output_xorg).unwrap();
+            let expected = vec!["lightdm", "lightdm-slick-greeter"];
+            assert_eq!(expected, parsed_dependants)
+        }
+        {
+            let  parse_dependants = parse_required_by(pacman_Qi_output_lightdm_slick).unwrap();
+            let expected : Vec<String> = Vec::new();
+            assert_eq!(expected, parse_dependants)
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,422 @@
+use std::{collections::BTreeSet, path::Path};
+
+use anyhow::{Context, bail};
+use regex::Regex;
+
+use super::{PackageId, PackageGroup, PackageGroupMap, pacman::{self, PacmanOptions}, systemd::{self, SystemctlOptions, Unit}, hooks::{Hook, HookKind}, runner::CommandRunner, config::{PackageConfiguration, TargetConfig}};
+
+/// Asks whether to proceed with the removal planned by a [`Plan`], see [`Plan::apply`]
+pub type ConfirmRemoval<'a> = &'a dyn Fn(&Plan) -> anyhow::Result<bool>;
+
+/// The differences between the packages configured for a target and the packages installed on the system.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    /// Configured packages that are not installed, indexed by the group configuring them
+    pub missing: PackageGroupMap,
+    /// Explicitly installed packages that are not part of any of the target's groups
+    pub untracked: BTreeSet<PackageId>,
+    /// Untracked packages required by other packages, which sync does not uninstall. Only set by [`Plan::ignore_required`]
+    pub ignored: BTreeSet<PackageId>,
+    /// Units of the target's groups that are not enabled. Only set by [`Plan::plan_units`]
+    pub enable: BTreeSet<Unit>,
+    /// Enabled units declared only by groups outside the target whose untracked packages are uninstalled. Only set by [`Plan::plan_units`]
+    pub disable: BTreeSet<Unit>,
+    /// Hooks of the groups whose packages are installed or uninstalled. Only set by [`Plan::plan_hooks`]
+    pub hooks: Vec<Hook>,
+}
+
+impl Plan {
+    /// Computes the plan for `target` from the set of explicitly installed packages.
+    pub fn new(installed: &BTreeSet<PackageId>, package_config: &PackageConfiguration, target: &TargetConfig) -> Self {
+        let missing = package_config.filter_groups(&target.root_groups)
+            .map(|(group_id, group)| {
+                let not_installed : BTreeSet<PackageId> = group.members
+                    .iter()
+                    .filter(|package| !installed.contains(*package))
+                    .cloned()
+                    .collect();
+                (group_id.clone(), PackageGroup::from_members(not_installed))
+            })
+            .filter(|(_, not_installed)| !not_installed.members.is_empty())
+            .collect();
+
+        let should_be_installed : BTreeSet<&PackageId> = package_config.packages_of_groups(&target.root_groups).collect();
+        let untracked = installed
+            .iter()
+            .filter(|package| !should_be_installed.contains(package))
+            .cloned()
+            .collect();
+
+        Self { missing, untracked, ..Default::default() }
+    }
+
+    /// Computes the units to enable and disable for `target`.
+    /// `query_enabled` gets the declared units and returns the enabled ones, like [`systemd::query_enabled`] does for the running system.
+    /// Only units of groups whose untracked packages are uninstalled are disabled, units not declared by any group never are.
+    /// Call it after [`Plan::ignore_required`], so groups whose packages are kept installed keep their units enabled.
+    pub fn plan_units<F>(&mut self, package_config: &PackageConfiguration, target: &TargetConfig, mut query_enabled: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&BTreeSet<Unit>) -> anyhow::Result<BTreeSet<Unit>>
+    {
+        let declared : BTreeSet<Unit> = package_config.filter_groups(&target.root_groups).flat_map(|(_, group)| group.units()).collect();
+        let foreign : BTreeSet<Unit> = package_config.iter_groups()
+            .filter(|(_, group)| !group.members.is_disjoint(&self.untracked))
+            .flat_map(|(_, group)| group.units())
+            .filter(|unit| !declared.contains(unit))
+            .collect();
+        if declared.is_empty() && foreign.is_empty() {
+            return Ok(());
+        }
+
+        let enabled = query_enabled(&declared.union(&foreign).cloned().collect())?;
+        self.enable = declared.difference(&enabled).cloned().collect();
+        self.disable = foreign.intersection(&enabled).cloned().collect();
+        Ok(())
+    }
+
+    /// Returns all missing packages across groups, without duplicates.
+    pub fn missing_packages(&self) -> BTreeSet<&PackageId> {
+        self.missing.values().flat_map(|group| &group.members).collect()
+    }
+
+    /// Moves the untracked packages required by other packages to `ignored`, so neither they nor their `pre_remove` hooks are planned for removal.
+    /// `required_by` gets the untracked packages and returns their dependants, like [`pacman::packages_required_by`] does for the running system.
+    pub fn ignore_required<F>(&mut self, required_by: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(Vec<PackageId>) -> anyhow::Result<Vec<(PackageId, Vec<PackageId>)>>
+    {
+        if self.untracked.is_empty() {
+            return Ok(());
+        }
+        for (package, dependants) in required_by(self.untracked.iter().cloned().collect())? {
+            if !dependants.is_empty() && self.untracked.remove(&package) {
+                self.ignored.insert(package);
+            }
+        }
+        Ok(())
+    }
+
+    /// Selects the untracked packages to import: those matching `pattern`, or all if it is `None`, except the ones matching any of the `exclude` globs.
+    pub fn untracked_matching(&self, pattern: Option<&Regex>, exclude: &[glob::Pattern]) -> BTreeSet<PackageId> {
+        self.untracked
+            .iter()
+            .filter(|package| pattern.is_none_or(|pattern| pattern.is_match(package)))
+            .filter(|package| !exclude.iter().any(|glob| glob.matches(package)))
+            .cloned()
+            .collect()
+    }
+
+    /// Resolves the hooks to run: `post_install` of the groups with missing packages
+    /// and `pre_remove` of the groups declaring untracked packages, resolved against `package_dir`.
+    pub fn plan_hooks(&mut self, package_config: &PackageConfiguration, package_dir: &Path) {
+        let post_install = self.missing.iter().filter_map(|(group_id, missing)| {
+            let (_, group) = package_config.find_group(group_id)?;
+            let script = group.post_install.as_ref()?;
+            Some(Hook { kind: HookKind::PostInstall, group: group_id.clone(), script: package_dir.join(script), packages: missing.members.clone() })
+        });
+        let pre_remove = package_config.iter_groups().filter_map(|(group_id, group)| {
+            let script = group.pre_remove.as_ref()?;
+            let packages : BTreeSet<PackageId> = group.members.intersection(&self.untracked).cloned().collect();
+            (!packages.is_empty()).then(|| Hook { kind: HookKind::PreRemove, group: group_id.clone(), script: package_dir.join(script), packages })
+        });
+        self.hooks = post_install.chain(pre_remove).collect();
+    }
+
+    /// Applies the plan by installing the missing packages, running the `post_install` hooks and enabling the units.
+    /// pacman prompts for confirmation before installing on the system selected by `options`. Declining or a failing pacman stops the sync with an error,
+    /// so units are only enabled once the packages providing them are installed.
+    ///
+    /// If `confirm_removal` is passed and confirms the plan, the units of other groups are disabled, the `pre_remove` hooks run
+    /// and the untracked packages are uninstalled without prompting again. Call [`Plan::ignore_required`] first, pacman refuses to
+    /// uninstall packages required by others.
+    /// A failing hook does not stop the sync, the failures of all groups are returned as one error at the end.
+    pub fn apply(&self, runner: &dyn CommandRunner, options: &PacmanOptions, systemctl_options: &SystemctlOptions, confirm_removal: Option<ConfirmRemoval>) -> anyhow::Result<()> {
+        let mut hook_failures = Vec::new();
+        let missing_packages = self.missing_packages();
+        if !missing_packages.is_empty() {
+            pacman::install_packages(runner, options, missing_packages).context("Failed to install missing packages")?;
+        }
+
+        for hook in self.hooks.iter().filter(|hook| hook.kind == HookKind::PostInstall) {
+            run_hook(runner, hook, systemctl_options, &mut hook_failures);
+        }
+
+        // units may be provided by the packages just installed
+        if !self.enable.is_empty() {
+            systemd::enable_units(runner, systemctl_options, &self.enable).context("Failed to enable units")?;
+        }
+
+        let Some(confirm_removal) = confirm_removal else {
+            return hook_result(hook_failures);
+        };
+        if self.disable.is_empty() && self.untracked.is_empty() {
+            return hook_result(hook_failures);
+        }
+        if !confirm_removal(self)? {
+            log::info!("Removal of untracked packages declined");
+            return hook_result(hook_failures);
+        }
+        if !self.disable.is_empty() {
+            systemd::disable_units(runner, systemctl_options, &self.disable).context("Failed to disable units")?;
+        }
+        if !self.untracked.is_empty() {
+            for hook in self.hooks.iter().filter(|hook| hook.kind == HookKind::PreRemove) {
+                run_hook(runner, hook, systemctl_options, &mut hook_failures);
+            }
+            pacman::uninstall_packages(runner, options, &self.untracked).context("Failed to uninstall untracked packages")?;
+        }
+
+        hook_result(hook_failures)
+    }
+
+    /// Applies the plan to a fresh system at `root` by installing the missing packages with pacstrap,
+    /// then running the `post_install` hooks and enabling the units like [`Plan::apply`] does.
+    pub fn bootstrap(&self, runner: &dyn CommandRunner, root: &Path, systemctl_options: &SystemctlOptions) -> anyhow::Result<()> {
+        pacman::pacstrap_packages(runner, root, self.missing_packages()).context("Failed to bootstrap system")?;
+        let mut hook_failures = Vec::new();
+        for hook in self.hooks.iter().filter(|hook| hook.kind == HookKind::PostInstall) {
+            run_hook(runner, hook, systemctl_options, &mut hook_failures);
+        }
+        if !self.enable.is_empty() {
+            systemd::enable_units(runner, systemctl_options, &self.enable).context("Failed to enable units")?;
+        }
+        hook_result(hook_failures)
+    }
+}
+
+/// Fails with the recorded hook failures, if there are any.
+fn hook_result(failures: Vec<String>) -> anyhow::Result<()> {
+    if !failures.is_empty() {
+        bail!("Hooks failed for {}", failures.join(", "));
+    }
+    Ok(())
+}
+
+/// Runs a hook on the system selected by `systemctl_options`, inside its root if one is set, recording its group if it fails.
+fn run_hook(runner: &dyn CommandRunner, hook: &Hook, systemctl_options: &SystemctlOptions, failures: &mut Vec<String>) {
+    if let Err(err) = hook.run(runner, systemctl_options.root.as_deref()) {
+        log::error!("{} hook of group '{}' failed: {:#}", hook.kind, hook.group, err);
+        failures.push(format!("group '{}' ({})", hook.group, hook.kind));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use crate::test_util::{package_config_from_groups, FakeRunner};
+
+    fn package_config() -> PackageConfiguration {
+        let mut groups = BTreeMap::new();
+        groups.insert("base".to_owned(), PackageGroup::from_members(["bat".to_owned(), "helix".to_owned()].into()));
+        let mut dev = PackageGroup::from_members(["rustup".to_owned(), "helix".to_owned()].into());
+        dev.post_install = Some("hooks/rustup.sh".into());
+        groups.insert("dev".to_owned(), dev);
+        let mut server = PackageGroup::from_members(["nginx".to_owned()].into());
+        server.services.insert("nginx.service".to_owned());
+        server.pre_remove = Some("hooks/nginx.sh".into());
+        groups.insert("server".to_owned(), server);
+        let mut desktop = PackageGroup::from_members(["pipewire".to_owned()].into());
+        desktop.user_services.insert("pipewire.socket".to_owned());
+        groups.insert("desktop".to_owned(), desktop);
+
+        package_config_from_groups(groups)
+    }
+
+    #[test]
+    fn test_plan_missing_and_untracked() {
+        let target = TargetConfig::from_root_groups(["base".to_owned(), "dev".to_owned()].into());
+        let installed : BTreeSet<PackageId> = ["bat".to_owned(), "nginx".to_owned(), "htop".to_owned()].into();
+
+        let plan = Plan::new(&installed, &package_config(), &target);
+
+        let expected_missing : PackageGroupMap = [
+            ("base".to_owned(), PackageGroup::from_members(["helix".to_owned()].into())),
+            ("dev".to_owned(), PackageGroup::from_members(["helix".to_owned(), "rustup".to_owned()].into())),
+        ].into();
+        assert_eq!(expected_missing, plan.missing);
+        assert_eq!(2, plan.missing_packages().len());
+
+        let expected_untracked : BTreeSet<PackageId> = ["htop".to_owned(), "nginx".to_owned()].into();
+        assert_eq!(expected_untracked, plan.untracked);
+    }
+
+    #[test]
+    fn test_plan_in_sync() {
+        let target = TargetConfig::from_root_groups(["server".to_owned()].into());
+        let installed : BTreeSet<PackageId> = ["nginx".to_owned()].into();
+
+        let plan = Plan::new(&installed, &package_config(), &target);
+        assert_eq!(Plan::default(), plan);
+    }
+
+    #[test]
+    fn test_untracked_matching() {
+        let plan = Plan { untracked: ["htop".to_owned(), "python-pip".to_owned(), "python-numpy".to_owned()].into(), ..Default::default() };
+        let pattern = Regex::new("^python-").unwrap();
+        let exclude = [glob::Pattern::new("*-pip").unwrap()];
+
+        assert_eq!(BTreeSet::from(["python-numpy".to_owned(), "python-pip".to_owned()]), plan.untracked_matching(Some(&pattern), &[]));
+        assert_eq!(BTreeSet::from(["python-numpy".to_owned()]), plan.untracked_matching(Some(&pattern), &exclude));
+        assert_eq!(BTreeSet::from(["htop".to_owned(), "python-numpy".to_owned()]), plan.untracked_matching(None, &exclude));
+    }
+
+    #[test]
+    fn test_plan_hooks() {
+        let package_config = package_config();
+        let target = TargetConfig::from_root_groups(["base".to_owned(), "dev".to_owned()].into());
+        let installed : BTreeSet<PackageId> = ["bat".to_owned(), "nginx".to_owned(), "rustup".to_owned()].into();
+
+        let mut plan = Plan::new(&installed, &package_config, &target);
+        plan.plan_hooks(&package_config, Path::new("/packages"));
+        assert_eq!(vec![
+            Hook { kind: HookKind::PostInstall, group: "dev".to_owned(), script: "/packages/hooks/rustup.sh".into(), packages: ["helix".to_owned()].into() },
+            Hook { kind: HookKind::PreRemove, group: "server".to_owned(), script: "/packages/hooks/nginx.sh".into(), packages: ["nginx".to_owned()].into() },
+        ], plan.hooks);
+    }
+
+    #[test]
+    fn test_plan_hooks_interpolated() {
+        let mut kernel = PackageGroup::from_members(["${kernel}-headers".to_owned()].into());
+        kernel.pre_remove = Some("hooks/dkms.sh".into());
+        let package_config = package_config_from_groups([("kernel".to_owned(), kernel)].into());
+        let target = TargetConfig { root_groups: BTreeSet::new(), variables: [("kernel".to_owned(), "linux-lts".to_owned())].into() };
+        let package_config = package_config.interpolated(&target.root_groups, &target.variables).unwrap();
+        let installed : BTreeSet<PackageId> = ["linux-lts-headers".to_owned()].into();
+
+        // the members of groups outside the target are matched with the variables of the target
+        let mut plan = Plan::new(&installed, &package_config, &target);
+        plan.plan_hooks(&package_config, Path::new("/packages"));
+        assert_eq!(vec![
+            Hook { kind: HookKind::PreRemove, group: "kernel".to_owned(), script: "/packages/hooks/dkms.sh".into(), packages: ["linux-lts-headers".to_owned()].into() },
+        ], plan.hooks);
+    }
+
+    #[test]
+    fn test_apply() {
+        let package_config = package_config();
+        let target = TargetConfig::from_root_groups(["base".to_owned(), "dev".to_owned()].into());
+        let installed : BTreeSet<PackageId> = ["bat".to_owned(), "nginx".to_owned()].into();
+        let mut plan = Plan::new(&installed, &package_config, &target);
+        plan.plan_units(&package_config, &target, |_| Ok(BTreeSet::from([Unit::system("nginx.service".to_owned())]))).unwrap();
+        plan.plan_hooks(&package_config, Path::new("/packages"));
+
+        let runner = FakeRunner::default();
+        plan.apply(&runner, &PacmanOptions::default(), &SystemctlOptions::default(), None).unwrap();
+        assert_eq!(vec!["pacman -S helix rustup", "/packages/hooks/rustup.sh helix rustup"], *runner.commands.borrow());
+
+        let runner = FakeRunner::default();
+        plan.apply(&runner, &PacmanOptions::default(), &SystemctlOptions::default(), Some(&|_| Ok(true))).unwrap();
+        assert_eq!(vec![
+            "pacman -S helix rustup",
+            "/packages/hooks/rustup.sh helix rustup",
+            "systemctl disable nginx.service",
+            "/packages/hooks/nginx.sh nginx",
+            "pacman -Rs --noconfirm nginx",
+        ], *runner.commands.borrow());
+
+        // on another system the hooks run inside its root
+        let package_dir = tempfile::tempdir().unwrap();
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(package_dir.path().join("hooks")).unwrap();
+        std::fs::write(package_dir.path().join("hooks/rustup.sh"), "#!/bin/sh\n").unwrap();
+        plan.plan_hooks(&package_config, package_dir.path());
+        let systemctl_options = SystemctlOptions { root: Some(root.path().to_owned()) };
+        let runner = FakeRunner::default();
+        plan.apply(&runner, &PacmanOptions::default(), &systemctl_options, None).unwrap();
+        assert_eq!(vec![
+            "pacman -S helix rustup".to_owned(),
+            format!("arch-chroot {} /tmp/impaccable-post_install-rustup.sh helix rustup", root.path().to_string_lossy()),
+        ], *runner.commands.borrow());
+        assert!(!root.path().join("tmp/impaccable-post_install-rustup.sh").exists());
+    }
+
+    #[test]
+    fn test_apply_removal_declined_or_failed() {
+        let package_config = package_config();
+        let target = TargetConfig::from_root_groups(["base".to_owned()].into());
+        let installed : BTreeSet<PackageId> = ["bat".to_owned(), "helix".to_owned(), "nginx".to_owned()].into();
+        let mut plan = Plan::new(&installed, &package_config, &target);
+        plan.plan_hooks(&package_config, Path::new("/packages"));
+
+        // the plan to confirm is the one applied
+        let runner = FakeRunner::default();
+        let confirm = |confirmed: &Plan| {
+            assert_eq!(BTreeSet::from(["nginx".to_owned()]), confirmed.untracked);
+            Ok(false)
+        };
+        plan.apply(&runner, &PacmanOptions::default(), &SystemctlOptions::default(), Some(&confirm)).unwrap();
+        assert!(runner.commands.borrow().is_empty());
+
+        let runner = FakeRunner::failing(vec!["pacman -Rs"]);
+        let result = plan.apply(&runner, &PacmanOptions::default(), &SystemctlOptions::default(), Some(&|_| Ok(true)));
+        assert!(result.is_err());
+        assert_eq!(vec!["/packages/hooks/nginx.sh nginx", "pacman -Rs --noconfirm nginx"], *runner.commands.borrow());
+    }
+
+    #[test]
+    fn test_ignore_required() {
+        let package_config = package_config();
+        let target = TargetConfig::from_root_groups(["base".to_owned()].into());
+        let installed : BTreeSet<PackageId> = ["bat".to_owned(), "helix".to_owned(), "nginx".to_owned(), "htop".to_owned()].into();
+        let mut plan = Plan::new(&installed, &package_config, &target);
+
+        // nginx is required by another package, so neither it nor the pre_remove hook of its group are planned
+        plan.ignore_required(|packages| Ok(packages.into_iter().map(|package| {
+            let dependants = if package == "nginx" { vec!["certbot-nginx".to_owned()] } else { Vec::new() };
+            (package, dependants)
+        }).collect())).unwrap();
+        plan.plan_hooks(&package_config, Path::new("/packages"));
+        assert_eq!(BTreeSet::from(["htop".to_owned()]), plan.untracked);
+        assert_eq!(BTreeSet::from(["nginx".to_owned()]), plan.ignored);
+        assert!(plan.hooks.is_empty());
+    }
+
+    #[test]
+    fn test_apply_failed_install() {
+        let package_config = package_config();
+        let target = TargetConfig::from_root_groups(["server".to_owned(), "dev".to_owned()].into());
+        let mut plan = Plan::new(&BTreeSet::new(), &package_config, &target);
+        plan.plan_units(&package_config, &target, |_| Ok(BTreeSet::new())).unwrap();
+        plan.plan_hooks(&package_config, Path::new("/packages"));
+        assert!(!plan.enable.is_empty());
+
+        // declining the installation exits with 1 as well, neither hooks nor units run for packages that are not installed
+        let runner = FakeRunner::failing(vec!["pacman -S"]);
+        assert!(plan.apply(&runner, &PacmanOptions::default(), &SystemctlOptions::default(), Some(&|_| Ok(true))).is_err());
+        assert_eq!(vec!["pacman -S helix nginx rustup"], *runner.commands.borrow());
+    }
+
+    #[test]
+    fn test_plan_units() {
+        let target = TargetConfig::from_root_groups(["server".to_owned()].into());
+        let mut plan = Plan { untracked: ["pipewire".to_owned()].into(), ..Default::default() };
+        let mut queried = BTreeSet::new();
+        // stand-in for systemctl, pipewire.socket is enabled
+        plan.plan_units(&package_config(), &target, |units| {
+            queried = units.clone();
+            Ok(units.iter().filter(|unit| unit.name == "pipewire.socket").cloned().collect())
+        }).unwrap();
+
+        assert_eq!(BTreeSet::from([Unit::system("nginx.service".to_owned()), Unit::user("pipewire.socket".to_owned())]), queried);
+        assert_eq!(BTreeSet::from([Unit::system("nginx.service".to_owned())]), plan.enable);
+        assert_eq!(BTreeSet::from([Unit::user("pipewire.socket".to_owned())]), plan.disable);
+    }
+
+    #[test]
+    fn test_plan_units_ignored() {
+        let target = TargetConfig::from_root_groups(["server".to_owned()].into());
+        let mut plan = Plan { untracked: ["pipewire".to_owned()].into(), ..Default::default() };
+        // pipewire is required by another package, so it stays installed and its unit enabled
+        plan.ignore_required(|packages| Ok(packages.into_iter().map(|package| (package, vec!["wireplumber".to_owned()])).collect())).unwrap();
+        let mut queried = BTreeSet::new();
+        plan.plan_units(&package_config(), &target, |units| {
+            queried = units.clone();
+            Ok(units.clone())
+        }).unwrap();
+
+        assert_eq!(BTreeSet::from(["pipewire".to_owned()]), plan.ignored);
+        assert_eq!(BTreeSet::from([Unit::system("nginx.service".to_owned())]), queried);
+        assert!(plan.disable.is_empty());
+    }
+}
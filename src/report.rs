@@ -0,0 +1,307 @@
+use std::{collections::{BTreeMap, BTreeSet}, fmt::Display, fs, path::Path};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use super::{PackageId, config::{Config, PackageConfiguration, TargetId}, plan::Plan, snapshot::Snapshot};
+
+/// The drift of a fleet of machines from their targets, computed from snapshots.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FleetReport {
+    pub machines: Vec<MachineReport>,
+    /// Packages untracked on many machines, candidates for a shared group
+    pub shared_candidates: Vec<SharedCandidate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MachineReport {
+    /// Name of the snapshot file without extension
+    pub name: String,
+    pub hostname: Option<String>,
+    pub target: Option<TargetId>,
+    pub missing: BTreeSet<PackageId>,
+    pub untracked: BTreeSet<PackageId>,
+    /// Why the machine could not be evaluated, e.g. an unknown target
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SharedCandidate {
+    pub package: PackageId,
+    /// Names of the machines the package is untracked on
+    pub machines: Vec<String>,
+}
+
+impl FleetReport {
+    /// Evaluates each snapshot against its target.
+    /// Snapshots without a target are evaluated against the target named like their hostname, if there is one.
+    /// Packages untracked on at least `min_machines` machines are suggested for a shared group.
+    pub fn new(snapshots: &[(String, Snapshot)], config: &Config, package_config: &PackageConfiguration, min_machines: usize) -> Self {
+        let machines : Vec<MachineReport> = snapshots
+            .iter()
+            .map(|(name, snapshot)| MachineReport::new(name, snapshot, config, package_config))
+            .collect();
+
+        let mut untracked_on : BTreeMap<&PackageId, Vec<String>> = BTreeMap::new();
+        for machine in &machines {
+            for package in &machine.untracked {
+                untracked_on.entry(package).or_default().push(machine.name.clone());
+            }
+        }
+        let mut shared_candidates : Vec<SharedCandidate> = untracked_on
+            .into_iter()
+            .filter(|(_, machines)| machines.len() >= min_machines)
+            .map(|(package, machines)| SharedCandidate { package: package.clone(), machines })
+            .collect();
+        // the most common packages first, ties sorted by name
+        shared_candidates.sort_by(|a, b| b.machines.len().cmp(&a.machines.len()).then_with(|| a.package.cmp(&b.package)));
+
+        Self { machines, shared_candidates }
+    }
+
+    /// Renders the report as a standalone HTML page.
+    pub fn render_html(&self) -> String {
+        let mut html = String::from(HTML_HEADER);
+        html.push_str("<h2>Machines</h2>\n<table>\n<tr><th>Machine</th><th>Hostname</th><th>Target</th><th>Missing</th><th>Untracked</th></tr>\n");
+        for machine in &self.machines {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td>",
+                escape_html(&machine.name),
+                escape_html(machine.hostname.as_deref().unwrap_or("-")),
+                escape_html(machine.target.as_deref().unwrap_or("-")),
+            ));
+            match &machine.error {
+                Some(error) => html.push_str(&format!("<td colspan=\"2\" class=\"error\">{}</td></tr>\n", escape_html(error))),
+                None => html.push_str(&format!(
+                    "<td>{}</td><td>{}</td></tr>\n",
+                    package_details(&machine.missing),
+                    package_details(&machine.untracked),
+                )),
+            }
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Candidates for a shared group</h2>\n");
+        if self.shared_candidates.is_empty() {
+            html.push_str("<p>None</p>\n");
+        } else {
+            html.push_str("<table>\n<tr><th>Package</th><th>Machines</th></tr>\n");
+            for candidate in &self.shared_candidates {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}: {}</td></tr>\n",
+                    escape_html(&candidate.package),
+                    candidate.machines.len(),
+                    escape_html(&candidate.machines.join(", ")),
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+impl MachineReport {
+    fn new(name: &str, snapshot: &Snapshot, config: &Config, package_config: &PackageConfiguration) -> Self {
+        let target_id = snapshot.target.clone()
+            .or_else(|| snapshot.hostname.clone().filter(|hostname| config.targets.contains_key(hostname)));
+        let mut report = Self {
+            name: name.to_owned(),
+            hostname: snapshot.hostname.clone(),
+            target: target_id.clone(),
+            missing: BTreeSet::new(),
+            untracked: BTreeSet::new(),
+            error: None,
+        };
+
+        let Some(target_id) = target_id else {
+            report.error = Some(String::from("Snapshot has no target and its hostname is not a target"));
+            return report;
+        };
+        let Some(target) = config.targets.get(&target_id) else {
+            report.error = Some(format!("Target '{}' not found", target_id));
+            return report;
+        };
+
+        let package_config = match package_config.interpolated(&target.root_groups, &config.target_variables(target)) {
+            Ok(package_config) => package_config,
+            Err(err) => {
+                report.error = Some(err.to_string());
+                return report;
+            }
+        };
+        let plan = Plan::new(&snapshot.explicitly_installed(), &package_config, target);
+        report.missing = plan.missing_packages().into_iter().cloned().collect();
+        report.untracked = plan.untracked;
+        report
+    }
+}
+
+impl Display for FleetReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name_width = self.machines.iter().map(|machine| machine.name.len()).chain(["Machine".len()]).max().unwrap_or_default();
+        let target_width = self.machines.iter().map(|machine| machine.target.as_deref().unwrap_or("-").len()).chain(["Target".len()]).max().unwrap_or_default();
+
+        writeln!(f, "{:name_width$}  {:target_width$}  {:>7}  {:>9}", "Machine", "Target", "Missing", "Untracked")?;
+        for machine in &self.machines {
+            write!(f, "{:name_width$}  {:target_width$}  ", machine.name, machine.target.as_deref().unwrap_or("-"))?;
+            match &machine.error {
+                Some(error) => writeln!(f, "error: {}", error)?,
+                None => writeln!(f, "{:>7}  {:>9}", machine.missing.len(), machine.untracked.len())?,
+            }
+        }
+
+        writeln!(f)?;
+        if self.shared_candidates.is_empty() {
+            writeln!(f, "No candidates for a shared group")?;
+        } else {
+            writeln!(f, "Candidates for a shared group:")?;
+            for candidate in &self.shared_candidates {
+                writeln!(f, "  {} (untracked on {}: {})", candidate.package, candidate.machines.len(), candidate.machines.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads all `.toml` snapshots in `dir`, named after their file stem and sorted by name.
+pub fn read_snapshots(dir: &Path) -> anyhow::Result<Vec<(String, Snapshot)>> {
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read snapshot directory '{}'", dir.to_string_lossy()))? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|extension| extension != "toml") {
+            continue;
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        snapshots.push((name, Snapshot::read(&path)?));
+    }
+    snapshots.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(snapshots)
+}
+
+const HTML_HEADER : &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>impaccable fleet report</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+.error { color: #b00; }
+</style>
+</head>
+<body>
+<h1>impaccable fleet report</h1>
+"#;
+
+/// Renders the package count, with the packages listed in a collapsible element.
+fn package_details(packages: &BTreeSet<PackageId>) -> String {
+    if packages.is_empty() {
+        return String::from("0");
+    }
+    let list : Vec<String> = packages.iter().map(|package| escape_html(package)).collect();
+    format!("<details><summary>{}</summary>{}</details>", packages.len(), list.join("<br>"))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::TargetConfig, test_util::{config, package_config, set, snapshot}};
+
+    fn fleet_report() -> FleetReport {
+        let package_config = package_config(&[("base", &["bat", "helix"])]);
+        let config = config([("desktop", TargetConfig::from_root_groups(set(&["base"])))]);
+
+        let snapshots = vec![
+            ("a".to_owned(), snapshot(Some("a"), Some("desktop"), &["bat", "firefox", "htop"])),
+            ("b".to_owned(), snapshot(Some("desktop"), None, &["bat", "helix", "firefox"])),
+            ("c".to_owned(), snapshot(Some("c"), None, &["firefox"])),
+        ];
+        FleetReport::new(&snapshots, &config, &package_config, 2)
+    }
+
+    #[test]
+    fn test_fleet_report() {
+        let report = fleet_report();
+
+        assert_eq!(BTreeSet::from(["helix".to_owned()]), report.machines[0].missing);
+        assert_eq!(BTreeSet::from(["firefox".to_owned(), "htop".to_owned()]), report.machines[0].untracked);
+        assert_eq!(Some("desktop".to_owned()), report.machines[1].target);
+        assert!(report.machines[1].missing.is_empty());
+        assert!(report.machines[2].error.is_some());
+        assert_eq!(vec![SharedCandidate { package: "firefox".to_owned(), machines: vec!["a".to_owned(), "b".to_owned()] }], report.shared_candidates);
+
+        let expected = "Machine  Target   Missing  Untracked
+a        desktop        1          2
+b        desktop        0          1
+c        -        error: Snapshot has no target and its hostname is not a target
+
+Candidates for a shared group:
+  firefox (untracked on 2: a, b)
+";
+        assert_eq!(expected, report.to_string());
+    }
+
+    #[test]
+    fn test_fleet_report_json() {
+        let json : serde_json::Value = serde_json::to_value(fleet_report()).unwrap();
+
+        assert_eq!(serde_json::json!({
+            "name": "a",
+            "hostname": "a",
+            "target": "desktop",
+            "missing": ["helix"],
+            "untracked": ["firefox", "htop"],
+        }), json["machines"][0]);
+        // the error is omitted for evaluated machines
+        assert!(json["machines"][1].get("error").is_none());
+        assert_eq!(serde_json::json!(null), json["machines"][2]["target"]);
+        assert_eq!(serde_json::json!([{ "package": "firefox", "machines": ["a", "b"] }]), json["shared_candidates"]);
+    }
+
+    #[test]
+    fn test_render_html_escapes() {
+        let report = FleetReport {
+            machines: vec![MachineReport {
+                name: "<script>".to_owned(),
+                hostname: Some("a&b".to_owned()),
+                target: None,
+                missing: BTreeSet::new(),
+                untracked: set(&["\"quoted\""]),
+                error: None,
+            }],
+            shared_candidates: Vec::new(),
+        };
+        let html = report.render_html();
+
+        assert!(html.contains("<tr><td>&lt;script&gt;</td><td>a&amp;b</td><td>-</td><td>0</td><td><details><summary>1</summary>&quot;quoted&quot;</details></td></tr>"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<p>None</p>"));
+    }
+
+    #[test]
+    fn test_read_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.toml"), "hostname = \"b\"\n").unwrap();
+        std::fs::write(dir.path().join("a.toml"), "target = \"desktop\"\n\n[explicit.bat]\nversion = \"1.0-1\"\n").unwrap();
+        // files without the toml extension and directories are skipped
+        std::fs::write(dir.path().join("notes.txt"), "not a snapshot").unwrap();
+        std::fs::create_dir(dir.path().join("c.toml")).unwrap();
+
+        let snapshots = read_snapshots(dir.path()).unwrap();
+        let names : Vec<&str> = snapshots.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(vec!["a", "b"], names);
+        assert_eq!(snapshot(None, Some("desktop"), &["bat"]), snapshots[0].1);
+        assert_eq!(Some("b".to_owned()), snapshots[1].1.hostname);
+
+        std::fs::write(dir.path().join("broken.toml"), "explicit = 1").unwrap();
+        assert!(read_snapshots(dir.path()).is_err());
+        assert!(read_snapshots(&dir.path().join("missing")).is_err());
+    }
+}
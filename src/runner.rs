@@ -0,0 +1,27 @@
+use std::{io, process::{Command, ExitStatus, Output}};
+
+/// Runs the commands built by the modules interacting with external tools, e.g. pacman and systemctl.
+/// [`SystemRunner`] runs them on the system, tests substitute a fake to check the commands without changing the system.
+pub trait CommandRunner {
+    /// Runs the command with the stdio it was configured with, by default inherited, and waits for it to exit.
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus>;
+
+    /// Runs the command, capturing stdout and stderr.
+    fn output(&self, command: &mut Command) -> io::Result<Output>;
+}
+
+/// Runs commands as child processes, logging the command lines so failed runs can be reproduced.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        log::debug!("Running {:?}", command);
+        command.status()
+    }
+
+    fn output(&self, command: &mut Command) -> io::Result<Output> {
+        log::debug!("Running {:?}", command);
+        command.output()
+    }
+}
@@ -0,0 +1,115 @@
+use std::{collections::{BTreeMap, BTreeSet}, fs, path::Path};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::{PackageId, pacman::{self, PacmanOptions}, runner::CommandRunner, config::TargetId};
+
+/// The packages installed on a system at a point in time.
+/// Plans can be computed from a snapshot instead of the running system, e.g. to review the drift of another machine.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Hostname of the system the snapshot was taken on
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub hostname: Option<String>,
+    /// Target the system is expected to match
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub target: Option<TargetId>,
+    /// Explicitly installed packages
+    #[serde(default)]
+    pub explicit: BTreeMap<PackageId, ExplicitPackage>,
+    /// Versions of the packages installed as dependencies
+    #[serde(default)]
+    pub dependencies: BTreeMap<PackageId, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExplicitPackage {
+    pub version: String,
+    /// Installed packages depending on this package
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    pub required_by: Vec<PackageId>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the packages installed on the system selected by `options`. The target is left unset.
+    pub fn query(runner: &dyn CommandRunner, options: &PacmanOptions) -> anyhow::Result<Self> {
+        let explicit_versions = pacman::query_explicitly_installed_versions(runner, options).context("Failed to query explicitly installed packages")?;
+        let dependencies = pacman::query_dependency_versions(runner, options).context("Failed to query dependencies")?;
+
+        let mut required_by : BTreeMap<PackageId, Vec<PackageId>> = if explicit_versions.is_empty() {
+            BTreeMap::new()
+        } else {
+            pacman::packages_required_by(runner, options, explicit_versions.keys().cloned().collect())
+                .context("Failed to query dependants")?
+                .into_iter()
+                .collect()
+        };
+        let explicit = explicit_versions
+            .into_iter()
+            .map(|(package, version)| {
+                let required_by = required_by.remove(&package).unwrap_or_default();
+                (package, ExplicitPackage { version, required_by })
+            })
+            .collect();
+
+        let hostname_path = options.system_root().unwrap_or_else(|| "/".into()).join("etc/hostname");
+        let hostname = fs::read_to_string(hostname_path).ok().map(|hostname| hostname.trim().to_owned());
+        Ok(Self { hostname, target: None, explicit, dependencies })
+    }
+
+    /// Reads a snapshot written by `impaccable snapshot`.
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let snapshot_string = fs::read_to_string(path).with_context(|| format!("Failed to read snapshot '{}'", path.to_string_lossy()))?;
+        toml::from_str(&snapshot_string).with_context(|| format!("Failed to parse snapshot '{}'", path.to_string_lossy()))
+    }
+
+    /// Returns the explicitly installed packages, like `pacman::query_explicitly_installed` does for the running system.
+    pub fn explicitly_installed(&self) -> BTreeSet<PackageId> {
+        self.explicit.keys().cloned().collect()
+    }
+
+    /// Gets the packages requiring the passed packages, like `pacman::packages_required_by` does for the running system.
+    /// Only explicitly installed packages are tracked, others are not required by any package.
+    pub fn packages_required_by(&self, packages: Vec<PackageId>) -> Vec<(PackageId, Vec<PackageId>)> {
+        packages
+            .into_iter()
+            .map(|package| {
+                let required_by = self.explicit.get(&package).map(|explicit| explicit.required_by.clone()).unwrap_or_default();
+                (package, required_by)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let snapshot_string = r#"hostname = "workstation"
+
+[explicit.bat]
+version = "0.24.0-1"
+
+[explicit.lightdm]
+version = "1:1.32.0-4"
+required_by = ["lightdm-slick-greeter"]
+
+[dependencies]
+glibc = "2.38-7"
+"#;
+        let snapshot : Snapshot = toml::from_str(snapshot_string).unwrap();
+        assert_eq!(Some("workstation".to_owned()), snapshot.hostname);
+        assert_eq!(BTreeSet::from(["bat".to_owned(), "lightdm".to_owned()]), snapshot.explicitly_installed());
+
+        let required_by = snapshot.packages_required_by(vec!["lightdm".to_owned(), "glibc".to_owned()]);
+        assert_eq!(vec![
+            ("lightdm".to_owned(), vec!["lightdm-slick-greeter".to_owned()]),
+            ("glibc".to_owned(), Vec::new()),
+        ], required_by);
+
+        assert_eq!(snapshot_string, toml::to_string_pretty(&snapshot).unwrap());
+    }
+}
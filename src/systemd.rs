@@ -0,0 +1,150 @@
+use std::{collections::BTreeSet, fmt::Display, path::PathBuf, process::Command};
+
+use anyhow::{Context, bail};
+
+use super::runner::CommandRunner;
+
+/// Name of a systemd unit, e.g. `docker.service`
+pub type UnitId = String;
+
+/// Whether a unit is managed by the system manager or by the user managers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UnitScope {
+    System,
+    User,
+}
+
+/// A unit declared by a package group
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Unit {
+    pub scope: UnitScope,
+    pub name: UnitId,
+}
+
+impl Unit {
+    pub fn system(name: UnitId) -> Self {
+        Self { scope: UnitScope::System, name }
+    }
+
+    pub fn user(name: UnitId) -> Self {
+        Self { scope: UnitScope::User, name }
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.scope {
+            UnitScope::System => write!(f, "{}", self.name),
+            UnitScope::User => write!(f, "{} (user)", self.name),
+        }
+    }
+}
+
+/// States of `systemctl is-enabled` that need no enabling.
+/// Static and generated units have no install section, so they cannot be enabled anyway.
+const ENABLED_STATES : [&str; 6] = ["enabled", "enabled-runtime", "alias", "static", "indirect", "generated"];
+
+/// Selects the system systemctl operates on, like [`crate::pacman::PacmanOptions`] does for pacman.
+/// The default operates on the running system.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemctlOptions {
+    /// Root of the system to operate on, passed as `--root`
+    pub root: Option<PathBuf>,
+}
+
+impl SystemctlOptions {
+    /// Creates a systemctl command with the options applied.
+    /// User units are operated on for all users with `--global`, as sync usually runs as root.
+    fn command(&self, scope: UnitScope) -> Command {
+        let mut command = Command::new("systemctl");
+        if let Some(root) = &self.root {
+            command.arg("--root").arg(root);
+        }
+        if scope == UnitScope::User {
+            command.arg("--global");
+        }
+        command
+    }
+}
+
+/// Queries which of the passed units are enabled. Units that are not installed are not enabled.
+pub fn query_enabled(runner: &dyn CommandRunner, options: &SystemctlOptions, units: &BTreeSet<Unit>) -> anyhow::Result<BTreeSet<Unit>> {
+    let mut enabled = BTreeSet::new();
+    // queried one by one, as systemctl stops at the first unit that is not installed
+    for unit in units {
+        let mut command = options.command(unit.scope);
+        command.arg("is-enabled").arg(&unit.name);
+        let output = runner.output(&mut command).context("Failed to run systemctl")?;
+        let state = String::from_utf8(output.stdout).context("Failed to parse systemctl stdout as utf8")?;
+        log::trace!("{} is {}", unit, state.trim());
+        if is_enabled_state(state.trim()) {
+            enabled.insert(unit.clone());
+        }
+    }
+    Ok(enabled)
+}
+
+fn is_enabled_state(state: &str) -> bool {
+    ENABLED_STATES.contains(&state)
+}
+
+/// Enables the passed units, without starting them.
+pub fn enable_units<'a, I>(runner: &dyn CommandRunner, options: &SystemctlOptions, units: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = &'a Unit>,
+{
+    run_for_units(runner, options, "enable", units)
+}
+
+/// Disables the passed units, without stopping them.
+pub fn disable_units<'a, I>(runner: &dyn CommandRunner, options: &SystemctlOptions, units: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = &'a Unit>,
+{
+    run_for_units(runner, options, "disable", units)
+}
+
+/// Runs `systemctl <verb>` once per scope with all units of that scope.
+fn run_for_units<'a, I>(runner: &dyn CommandRunner, options: &SystemctlOptions, verb: &str, units: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = &'a Unit>,
+{
+    let units : Vec<&Unit> = units.into_iter().collect();
+    for scope in [UnitScope::System, UnitScope::User] {
+        let names : Vec<&UnitId> = units.iter().filter(|unit| unit.scope == scope).map(|unit| &unit.name).collect();
+        if names.is_empty() {
+            continue;
+        }
+        let mut command = options.command(scope);
+        command.arg(verb).args(names);
+        let status = runner.status(&mut command).context("Failed to run systemctl")?;
+        if !status.success() {
+            bail!("systemctl {} exited with {}", verb, status);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_options_command() {
+        let options = SystemctlOptions { root: Some("/mnt".into()) };
+        let command = options.command(UnitScope::User);
+        let args : Vec<&OsStr> = command.get_args().collect();
+        assert_eq!(vec!["--root", "/mnt", "--global"], args);
+        assert_eq!(0, SystemctlOptions::default().command(UnitScope::System).get_args().count());
+    }
+
+    #[test]
+    fn test_is_enabled_state() {
+        assert!(is_enabled_state("enabled"));
+        assert!(is_enabled_state("static"));
+        assert!(!is_enabled_state("disabled"));
+        assert!(!is_enabled_state("masked"));
+        assert!(!is_enabled_state(""));
+    }
+}
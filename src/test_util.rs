@@ -0,0 +1,78 @@
+use std::{cell::RefCell, collections::{BTreeMap, BTreeSet}, io, os::unix::process::ExitStatusExt, process::{Command, ExitStatus, Output}};
+
+use super::{PackageGroup, PackageGroupMap, config::{Config, PackageConfiguration, PackageFile, TargetConfig}, runner::CommandRunner, snapshot::{ExplicitPackage, Snapshot}, variables::Variables};
+
+/// Creates a package configuration of a single file containing `groups`.
+pub fn package_config_from_groups(groups: PackageGroupMap) -> PackageConfiguration {
+    let mut package_config = PackageConfiguration::default();
+    package_config.files.insert("/packages/packages.toml".into(), PackageFile::from_groups(groups));
+    package_config
+}
+
+/// Creates a package configuration of a single file containing groups with the passed members.
+pub fn package_config(groups: &[(&str, &[&str])]) -> PackageConfiguration {
+    package_config_from_groups(groups
+        .iter()
+        .map(|(group_id, members)| (group_id.to_string(), PackageGroup::from_members(set(members))))
+        .collect())
+}
+
+/// Creates a config with the passed targets and no global variables.
+pub fn config<const N: usize>(targets: [(&str, TargetConfig); N]) -> Config {
+    Config {
+        package_dir: "packages".into(),
+        variables: Variables::new(),
+        targets: targets.into_iter().map(|(target_id, target)| (target_id.to_owned(), target)).collect(),
+        git: Default::default(),
+    }
+}
+
+/// Creates a snapshot with the passed explicitly installed packages, all at version `1.0-1`.
+pub fn snapshot(hostname: Option<&str>, target: Option<&str>, explicit: &[&str]) -> Snapshot {
+    Snapshot {
+        hostname: hostname.map(|hostname| hostname.to_owned()),
+        target: target.map(|target| target.to_owned()),
+        explicit: explicit.iter().map(|package| (package.to_string(), ExplicitPackage { version: "1.0-1".to_owned(), required_by: Vec::new() })).collect(),
+        dependencies: BTreeMap::new(),
+    }
+}
+
+/// Collects names into a set of owned strings.
+pub fn set(names: &[&str]) -> BTreeSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+/// Records the command lines it is asked to run instead of running them.
+/// Commands starting with one of the `failing` prefixes exit with 1, all others succeed without output.
+#[derive(Debug, Default)]
+pub struct FakeRunner {
+    pub commands: RefCell<Vec<String>>,
+    pub failing: Vec<&'static str>,
+}
+
+impl FakeRunner {
+    pub fn failing(failing: Vec<&'static str>) -> Self {
+        Self { failing, ..Default::default() }
+    }
+
+    fn run(&self, command: &Command) -> ExitStatus {
+        let command_line = std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let failed = self.failing.iter().any(|prefix| command_line.starts_with(prefix));
+        self.commands.borrow_mut().push(command_line);
+        ExitStatus::from_raw(if failed { 1 << 8 } else { 0 })
+    }
+}
+
+impl CommandRunner for FakeRunner {
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        Ok(self.run(command))
+    }
+
+    fn output(&self, command: &mut Command) -> io::Result<Output> {
+        Ok(Output { status: self.run(command), stdout: Vec::new(), stderr: Vec::new() })
+    }
+}
@@ -0,0 +1,186 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use super::{GroupId, PackageId, config::{Config, PackageConfiguration, TargetId}};
+
+/// Values of variables by name, e.g. `kernel = "linux-lts"`
+pub type Variables = BTreeMap<String, String>;
+
+/// Why a templated member could not be interpolated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterpolationError {
+    /// Names of the referenced variables that are not defined
+    Undefined(Vec<String>),
+    /// The interpolated member, which contains whitespace or starts with `-` and would not be passed to pacman as one package name
+    InvalidName(PackageId),
+}
+
+impl Display for InterpolationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterpolationError::Undefined(variables) => {
+                let variables : Vec<String> = variables.iter().map(|variable| format!("'{}'", variable)).collect();
+                write!(f, "Undefined variable {}", variables.join(", "))
+            },
+            InterpolationError::InvalidName(member) => write!(f, "Invalid package name '{}'", member),
+        }
+    }
+}
+
+/// Replaces the `${name}` references in `template` with the values of the variables.
+/// A `$` not followed by `{` is kept as it is. Returns the names of all undefined variables as error,
+/// or the interpolated member if the values made it contain whitespace or start with `-`.
+pub fn interpolate(template: &str, variables: &Variables) -> Result<String, InterpolationError> {
+    let mut interpolated = String::with_capacity(template.len());
+    let mut undefined = Vec::new();
+    let mut referenced = false;
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        interpolated.push_str(&rest[..start]);
+        let reference = &rest[start + 2..];
+        let Some(end) = reference.find('}') else {
+            // unterminated references are not interpolated
+            interpolated.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+        let name = &reference[..end];
+        referenced = true;
+        match variables.get(name) {
+            Some(value) => interpolated.push_str(value),
+            None => undefined.push(name.to_owned()),
+        }
+        rest = &reference[end + 1..];
+    }
+    interpolated.push_str(rest);
+
+    if !undefined.is_empty() {
+        return Err(InterpolationError::Undefined(undefined));
+    }
+    if referenced && (interpolated.starts_with('-') || interpolated.contains(char::is_whitespace)) {
+        return Err(InterpolationError::InvalidName(interpolated));
+    }
+    Ok(interpolated)
+}
+
+/// Finds the member of `group_id` that is `package` once interpolated, e.g. `${kernel}-headers` for `linux-lts-headers`,
+/// with the variables of the targets the group is a root group of, or the global variables if it is none.
+/// Returns `package` itself if it is a member as written or no member interpolates to it.
+pub fn resolve_member(config: &Config, package_config: &PackageConfiguration, group_id: &GroupId, package: &PackageId) -> PackageId {
+    let Some((_, group)) = package_config.find_group(group_id) else {
+        return package.clone();
+    };
+    if group.members.contains(package) {
+        return package.clone();
+    }
+    let mut variables : Vec<Variables> = config.targets_with_root_group(group_id)
+        .map(|target_id| config.target_variables(&config.targets[target_id]))
+        .collect();
+    if variables.is_empty() {
+        variables.push(config.variables.clone());
+    }
+    group.members
+        .iter()
+        .find(|member| variables.iter().any(|variables| interpolate(member, variables).is_ok_and(|interpolated| &interpolated == package)))
+        .unwrap_or(package)
+        .clone()
+}
+
+/// A member of a group that cannot be interpolated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidMember {
+    /// Target the group is a root group of. `None` for groups without target, which are checked against the global variables
+    /// but never synced, so callers should only warn about them
+    pub target: Option<TargetId>,
+    pub group: GroupId,
+    pub member: PackageId,
+    pub error: InterpolationError,
+}
+
+/// Finds the members referencing undefined variables or interpolating to invalid package names, for every target the group is a root group of.
+pub fn find_invalid(config: &Config, package_config: &PackageConfiguration) -> Vec<InvalidMember> {
+    let mut invalid_members = Vec::new();
+    for (group_id, group) in package_config.iter_groups() {
+        let mut targets : Vec<(Option<&TargetId>, Variables)> = config.targets_with_root_group(group_id)
+            .map(|target_id| (Some(target_id), config.target_variables(&config.targets[target_id])))
+            .collect();
+        if targets.is_empty() {
+            targets.push((None, config.variables.clone()));
+        }
+
+        for (target_id, variables) in &targets {
+            for member in &group.members {
+                let Err(error) = interpolate(member, variables) else {
+                    continue;
+                };
+                invalid_members.push(InvalidMember {
+                    target: target_id.cloned(),
+                    group: group_id.clone(),
+                    member: member.clone(),
+                    error,
+                });
+            }
+        }
+    }
+    invalid_members
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::TargetConfig, test_util::{config, package_config}};
+
+    #[test]
+    fn test_interpolate() {
+        let variables : Variables = [("kernel".to_owned(), "linux-lts".to_owned())].into();
+        assert_eq!(Ok("linux-lts-headers".to_owned()), interpolate("${kernel}-headers", &variables));
+        assert_eq!(Ok("bat".to_owned()), interpolate("bat", &variables));
+        assert_eq!(Ok("$kernel ${kernel".to_owned()), interpolate("$kernel ${kernel", &variables));
+        assert_eq!(Err(InterpolationError::Undefined(vec!["gpu".to_owned(), "suffix".to_owned()])), interpolate("${gpu}-${kernel}${suffix}", &variables));
+    }
+
+    #[test]
+    fn test_interpolate_invalid_name() {
+        let variables : Variables = [("kernel".to_owned(), "linux lts".to_owned()), ("flag".to_owned(), "-dd".to_owned())].into();
+        assert_eq!(Err(InterpolationError::InvalidName("linux lts-headers".to_owned())), interpolate("${kernel}-headers", &variables));
+        assert_eq!(Err(InterpolationError::InvalidName("-dd".to_owned())), interpolate("${flag}", &variables));
+        assert_eq!(Ok("linux-dd".to_owned()), interpolate("linux${flag}", &variables));
+    }
+
+    #[test]
+    fn test_resolve_member() {
+        let package_config = package_config(&[("kernel", &["${kernel}-headers", "linux-firmware"]), ("gpu", &["${gpu}"])]);
+        let lts = TargetConfig { root_groups: ["kernel".to_owned()].into(), variables: [("kernel".to_owned(), "linux-lts".to_owned())].into() };
+        let mut config = config([("lts", lts)]);
+        config.variables.insert("gpu".to_owned(), "mesa".to_owned());
+
+        let resolve = |group: &str, package: &str| resolve_member(&config, &package_config, &group.to_owned(), &package.to_owned());
+        assert_eq!("${kernel}-headers", resolve("kernel", "linux-lts-headers"));
+        assert_eq!("linux-firmware", resolve("kernel", "linux-firmware"));
+        // groups without target are interpolated with the global variables
+        assert_eq!("${gpu}", resolve("gpu", "mesa"));
+        assert_eq!("linux-headers", resolve("kernel", "linux-headers"));
+        assert_eq!("htop", resolve("missing", "htop"));
+    }
+
+    #[test]
+    fn test_find_invalid() {
+        let package_config = package_config(&[("kernel", &["${kernel}", "${kernel}-headers"]), ("gpu", &["${gpu}"])]);
+
+        let lts = TargetConfig { root_groups: ["kernel".to_owned()].into(), variables: [("kernel".to_owned(), "linux-lts".to_owned())].into() };
+        let server = TargetConfig { root_groups: ["kernel".to_owned()].into(), variables: Variables::new() };
+        let broken = TargetConfig { root_groups: ["kernel".to_owned()].into(), variables: [("kernel".to_owned(), "linux lts".to_owned())].into() };
+        let config = config([("lts", lts), ("server", server), ("broken", broken)]);
+
+        let invalid = find_invalid(&config, &package_config);
+        let found : Vec<(Option<&str>, &str, String)> = invalid.iter()
+            .map(|invalid| (invalid.target.as_deref(), invalid.member.as_str(), invalid.error.to_string()))
+            .collect();
+        assert_eq!(vec![
+            (None, "${gpu}", "Undefined variable 'gpu'".to_owned()),
+            (Some("broken"), "${kernel}", "Invalid package name 'linux lts'".to_owned()),
+            (Some("broken"), "${kernel}-headers", "Invalid package name 'linux lts-headers'".to_owned()),
+            (Some("server"), "${kernel}", "Undefined variable 'kernel'".to_owned()),
+            (Some("server"), "${kernel}-headers", "Undefined variable 'kernel'".to_owned()),
+        ], found);
+    }
+}